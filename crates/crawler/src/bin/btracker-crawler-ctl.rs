@@ -0,0 +1,59 @@
+//! Client for the `btracker-crawler` control socket (see `control_socket` config option)
+
+use clap::{Parser, Subcommand};
+use std::{
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    process::ExitCode,
+};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Opt {
+    /// Path to the crawler control socket
+    #[arg(short, long)]
+    socket: PathBuf,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Show the queue position, current torrent, banned hashes, I2P sessions and tracker health
+    Status,
+    /// Resolve given info-hash on the next iteration (begins immediately)
+    Resolve { info_hash: String },
+    /// Remove given info-hash from the ban list
+    Unban { info_hash: String },
+    /// Abort the torrent being resolved
+    Skip,
+    /// Begin new iteration without waiting for `sleep_seconds`
+    Wake,
+}
+
+fn main() -> ExitCode {
+    let opt = Opt::parse();
+    let command = match opt.command {
+        Command::Status => "status".into(),
+        Command::Resolve { info_hash } => format!("resolve {info_hash}"),
+        Command::Unban { info_hash } => format!("unban {info_hash}"),
+        Command::Skip => "skip".into(),
+        Command::Wake => "wake".into(),
+    };
+    let mut response = String::new();
+    if let Err(e) = UnixStream::connect(&opt.socket).and_then(|mut s| {
+        s.write_all(format!("{command}\n").as_bytes())?;
+        s.read_to_string(&mut response)
+    }) {
+        eprintln!("could not connect `{}`: {e}", opt.socket.to_string_lossy());
+        return ExitCode::FAILURE;
+    }
+    print!("{response}");
+    if response.starts_with("error:") {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -1,14 +1,26 @@
-mod preload;
-mod timeout;
-mod tracker;
+pub mod filter;
+pub mod history;
+pub mod hook;
+pub mod lifecycle;
+pub mod preload;
+pub mod timeout;
+pub mod tracker;
+pub mod validate;
+pub mod worker;
 
+use filter::Filter;
+use history::History;
+use hook::Hook;
+use lifecycle::Lifecycle;
 use preload::Preload;
 use serde::Deserialize;
 use serde_inline_default::serde_inline_default;
-use std::net::SocketAddr;
+use std::{net::SocketAddr, path::PathBuf};
 use timeout::Timeout;
 use tracker::Tracker;
 use url::Url;
+use validate::Validate;
+use worker::Worker;
 
 #[serde_inline_default]
 #[derive(Deserialize)]
@@ -48,4 +60,37 @@ pub struct Config {
 
     #[serde(default)]
     pub timeout: Timeout,
+
+    /// Swarm stats history settings
+    #[serde(default)]
+    pub history: History,
+
+    /// Retention policy for delisted or dead torrents
+    #[serde(default)]
+    pub lifecycle: Lifecycle,
+
+    /// Notify external handlers on the crawler events
+    pub hook: Option<Hook>,
+
+    /// Metadata sanity limits
+    #[serde(default)]
+    pub validate: Validate,
+
+    /// Policy to skip unwanted torrents
+    #[serde(default)]
+    pub filter: Filter,
+
+    /// Coordination with other crawler instances
+    #[serde(default)]
+    pub worker: Worker,
+
+    /// Listen for the admin commands on given Unix socket path
+    ///
+    /// * use `btracker-crawler-ctl` to connect
+    pub control_socket: Option<PathBuf>,
+
+    /// Serve Prometheus metrics on given address (e.g. `127.0.0.1:9100`)
+    ///
+    /// * the endpoint is `/metrics`
+    pub metrics_listen: Option<SocketAddr>,
 }
@@ -0,0 +1,27 @@
+use serde::Deserialize;
+
+/// Skip unwanted torrents before the content preload
+///
+/// * skipped info-hashes are not resolved again until restart, to apply the updated policy
+#[derive(Default, Deserialize)]
+pub struct Filter {
+    /// Skip torrents with the `private` flag
+    #[serde(default)]
+    pub skip_private: bool,
+
+    /// Skip torrents with name match the `regex` pattern
+    pub name_deny: Option<String>,
+
+    /// Skip torrents with any file path match the `regex` pattern
+    pub file_deny: Option<String>,
+
+    /// Skip torrents with total size (bytes) less than given value
+    pub min_size: Option<u64>,
+
+    /// Skip torrents with total size (bytes) more than given value
+    pub max_size: Option<u64>,
+
+    /// Skip torrents contain any file with given extension (e.g. `exe`, `lnk`)
+    #[serde(default)]
+    pub extensions: Vec<String>,
+}
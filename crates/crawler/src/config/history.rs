@@ -0,0 +1,39 @@
+use serde::Deserialize;
+use serde_inline_default::serde_inline_default;
+
+#[serde_inline_default]
+#[derive(Deserialize)]
+pub struct History {
+    /// Record the full-scrape swarm stats (seeders, leechers, downloaded) per info-hash
+    ///
+    /// * the data is stored in the `.history` directory of the `preload` path
+    /// * every info-hash has own file, appended when the stats change after `interval_seconds`,
+    ///   so the disk writes grow with the listed info-hashes count
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Min interval between the info-hash records in seconds
+    ///
+    /// * unchanged stats are never recorded
+    #[serde_inline_default(3600)]
+    pub interval_seconds: u64,
+
+    /// Downsample records older than this value to one (max) record per day
+    #[serde_inline_default(7)]
+    pub raw_days: u64,
+
+    /// Remove records older than this value
+    #[serde_inline_default(365)]
+    pub retention_days: u64,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_seconds: 3600,
+            raw_days: 7,
+            retention_days: 365,
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::hook::Kind;
+use serde::Deserialize;
+use serde_inline_default::serde_inline_default;
+use std::{num::NonZeroUsize, path::PathBuf};
+use url::Url;
+
+#[serde_inline_default]
+#[derive(Deserialize)]
+pub struct Hook {
+    /// Run local executable on event, with following arguments:
+    /// `{event} {info_hash} {name} [path..]`
+    ///
+    /// * the same values are available in the `BTRACKER_EVENT`, `BTRACKER_INFO_HASH`,
+    ///   `BTRACKER_NAME` and `BTRACKER_PATHS` (new line separated) environment variables
+    pub command: Option<PathBuf>,
+
+    /// POST JSON event payload to given URL (`http://` or `https://`)
+    pub webhook: Option<Url>,
+
+    /// Handle the listed events only (all events by default)
+    ///
+    /// * `resolved`, `rejected`, `skipped`, `preload_failed`, `banned`,
+    ///   `delisted`, `dead`, `dropped`, `archived`, `deleted`
+    #[serde(default)]
+    pub events: Vec<Kind>,
+
+    /// Max events to keep in the queue, new events are skipped on overflow
+    ///
+    /// * must be greater than 0
+    #[serde_inline_default(NonZeroUsize::new(1000).unwrap())]
+    pub queue: NonZeroUsize,
+
+    /// Retry failed delivery given times
+    #[serde_inline_default(3)]
+    pub retries: u32,
+
+    /// Delay before the next retry, multiplied by the attempt number
+    #[serde_inline_default(5)]
+    pub retry_delay_seconds: u64,
+
+    /// Timeout of the command execution or the webhook request
+    #[serde_inline_default(10)]
+    pub timeout_seconds: u64,
+}
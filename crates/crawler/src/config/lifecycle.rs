@@ -0,0 +1,40 @@
+use serde::Deserialize;
+use serde_inline_default::serde_inline_default;
+use std::path::PathBuf;
+
+#[serde_inline_default]
+#[derive(Deserialize)]
+pub struct Lifecycle {
+    /// Mark committed torrents as delisted (by the full-scrape sources) or dead (no seeders)
+    ///
+    /// * the check is postponed while any of the full-scrape sources fails to respond
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Check the committed torrents once in given seconds
+    #[serde_inline_default(3600)]
+    pub interval_seconds: u64,
+
+    /// Drop preloaded content of the torrents inactive for given days
+    pub drop_content_days: Option<u64>,
+
+    /// Archive (or delete, if `archive` is not set) the torrents inactive for given days
+    pub expire_days: Option<u64>,
+
+    /// Directory to move expired torrents to
+    ///
+    /// * should be located on the same filesystem as the `preload.path`
+    pub archive: Option<PathBuf>,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_seconds: 3600,
+            drop_content_days: None,
+            expire_days: None,
+            archive: None,
+        }
+    }
+}
@@ -8,12 +8,18 @@ pub struct Preload {
     /// * it's probably the same location as `public` dir for the `btracker-http` frontend
     pub path: PathBuf,
 
+    /// Directory path to store preloaded content apart from the `.torrent` files
+    ///
+    /// * e.g. large cold disk, while the `path` is on the fast one
+    /// * the `path` is used by default
+    pub content_path: Option<PathBuf>,
+
     /// Preload content file (names) match `regex` pattern
     /// * see also `max_filesize`, `max_filesize` options
     ///
     /// ## Example:
     ///
-    /// ```
+    /// ``` text
     /// \.(png|gif|jpeg|jpg|webp|svg|log|nfo|txt)$
     /// ```
     pub regex: Option<String>,
@@ -23,4 +29,16 @@ pub struct Preload {
 
     /// Max count of preloaded files per torrent (match `regex`)
     pub max_filecount: Option<usize>,
+
+    /// Directory path to keep the deduplicated preloaded files
+    ///
+    /// * identical files of different torrents are stored once, as hardlinks
+    /// * should be located on the same filesystem as `content_path` (or `path`), files are copied otherwise
+    pub blobs: Option<PathBuf>,
+
+    /// Store torrents in the sharded `ab/cd/abcd...` directory tree instead of the flat root
+    ///
+    /// * recommended for large indexes, use `btracker-fs migrate` to convert existing data
+    #[serde(default)]
+    pub sharded: bool,
 }
@@ -1,5 +1,5 @@
-mod announce;
-mod scrape;
+pub mod announce;
+pub mod scrape;
 
 use announce::{Default, I2p};
 use scrape::Scrape;
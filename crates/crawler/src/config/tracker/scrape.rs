@@ -11,8 +11,16 @@ pub struct Scrape {
     /// URL to the BEP 48 / Full Scrape
     ///
     /// * supports HTTP trackers only
+    /// * supports 20 bytes info-hashes only (v1, or the truncated v2 of BEP 52),
+    ///   other entries are dropped with warning
     pub url: Url,
 
+    /// Source name to tag the listed info-hashes with, e.g. `music`
+    ///
+    /// * the `url` is used by default
+    /// * the public frontends filter the index by this value
+    pub name: Option<String>,
+
     /// How long to wait for tracker full scrape response
     #[serde_inline_default(5)]
     pub timeout: u64,
@@ -0,0 +1,33 @@
+use serde::Deserialize;
+use serde_inline_default::serde_inline_default;
+
+const MAX_FILES: usize = 100_000;
+const MAX_METADATA_SIZE: usize = 10 * 1024 * 1024;
+
+#[serde_inline_default]
+#[derive(Deserialize)]
+pub struct Validate {
+    /// Reject torrents with more files than given value
+    #[serde_inline_default(MAX_FILES)]
+    pub max_files: usize,
+
+    /// Reject torrents with metadata size (bytes) more than given value
+    #[serde_inline_default(MAX_METADATA_SIZE)]
+    pub max_metadata_size: usize,
+
+    /// Keep rejected metadata in the `.quarantine` directory of the `preload.path`
+    ///
+    /// * all rejected info-hashes are listed in the `.failures` file with the reason
+    #[serde(default)]
+    pub quarantine: bool,
+}
+
+impl Default for Validate {
+    fn default() -> Self {
+        Self {
+            max_files: MAX_FILES,
+            max_metadata_size: MAX_METADATA_SIZE,
+            quarantine: false,
+        }
+    }
+}
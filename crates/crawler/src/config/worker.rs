@@ -0,0 +1,34 @@
+use serde::Deserialize;
+use serde_inline_default::serde_inline_default;
+
+#[serde_inline_default]
+#[derive(Deserialize)]
+pub struct Worker {
+    /// Share the `preload` path with other crawler instances (e.g. on the NFS mount)
+    ///
+    /// * every info-hash is locked by one worker at once,
+    ///   the leases are stored in the `.lease` directory of the `preload` path
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Unique worker name, `{hostname}-{pid}` by default
+    pub name: Option<String>,
+
+    /// How long the info-hash lease is valid without renewal
+    ///
+    /// * the lease is renewed before the preload, so it must be greater than
+    ///   the `timeout.add_torrent_seconds` and the `timeout.torrent_preload_seconds`
+    /// * expired lease of the interrupted worker is taken over by others
+    #[serde_inline_default(600)]
+    pub lease_seconds: u64,
+}
+
+impl Default for Worker {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            name: None,
+            lease_seconds: 600,
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use crate::tracker::{self, I2pSession};
+use librqbit::dht::Id20;
+use log::*;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write as _,
+    io::{Error, ErrorKind},
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::UnixListener,
+    sync::{Notify, RwLock},
+};
+
+/// Crawler state shared with the control socket
+#[derive(Default)]
+pub struct State {
+    /// Total info-hashes in the current queue
+    pub queue_total: usize,
+    /// Position of the info-hash being processed in the current queue
+    pub queue_position: usize,
+    /// Info-hash being resolved
+    pub current: Option<String>,
+    /// Banned info-hashes snapshot
+    pub banned: Vec<String>,
+    /// Info-hashes requested to resolve on the next iteration
+    pub force: Vec<Id20>,
+    /// Info-hashes requested to remove from the ban list
+    pub unban: Vec<Id20>,
+}
+
+#[derive(Default)]
+pub struct Control {
+    pub state: Mutex<State>,
+    /// Abort the torrent being resolved
+    pub skip: Notify,
+    /// Begin new iteration without waiting for `sleep_seconds`
+    pub wake: Notify,
+}
+
+impl Control {
+    /// Update the banned info-hashes snapshot
+    pub fn banned(&self, ban: &HashSet<Id20>) {
+        self.state.lock().unwrap().banned = ban.iter().map(|i| i.as_string()).collect()
+    }
+
+    /// Update the current queue position, `None` on the queue completed
+    pub fn position(&self, position: usize, current: Option<String>) {
+        let mut s = self.state.lock().unwrap();
+        s.queue_position = position;
+        s.current = current
+    }
+
+    /// Take info-hashes requested to resolve
+    pub fn take_force(&self) -> Vec<Id20> {
+        std::mem::take(&mut self.state.lock().unwrap().force)
+    }
+
+    /// Take info-hashes requested to unban
+    pub fn take_unban(&self) -> Vec<Id20> {
+        std::mem::take(&mut self.state.lock().unwrap().unban)
+    }
+}
+
+/// Listen for the control commands on the Unix `socket`
+///
+/// * one command per connection, see `btracker-crawler-ctl --help`
+pub fn listen(
+    socket: &Path,
+    control: Arc<Control>,
+    tracker: Arc<tracker::Buffer>,
+    i2p: Arc<RwLock<HashMap<String, I2pSession>>>,
+) -> std::io::Result<()> {
+    if socket.exists() {
+        // cleanup after unexpected shutdown, but keep the socket of the running instance
+        match std::os::unix::net::UnixStream::connect(socket) {
+            Ok(_) => {
+                return Err(Error::new(
+                    ErrorKind::AddrInUse,
+                    format!(
+                        "control socket `{}` is in use by another instance",
+                        socket.to_string_lossy()
+                    ),
+                ));
+            }
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => std::fs::remove_file(socket)?,
+            Err(e) => return Err(e),
+        }
+    }
+    let listener = UnixListener::bind(socket)?;
+    info!("control socket listening on {}", socket.to_string_lossy());
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = match listener.accept().await {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!("[control] accept failed: {e}");
+                    continue;
+                }
+            };
+            let control = control.clone();
+            let tracker = tracker.clone();
+            let i2p = i2p.clone();
+            tokio::spawn(async move {
+                let (r, mut w) = stream.into_split();
+                let mut line = String::new();
+                if let Err(e) = BufReader::new(r).read_line(&mut line).await {
+                    warn!("[control] read failed: {e}");
+                    return;
+                }
+                debug!("[control] command `{}`", line.trim());
+                let response = handle(line.trim(), &control, &tracker, &i2p).await;
+                if let Err(e) = w.write_all(response.as_bytes()).await {
+                    warn!("[control] write failed: {e}")
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+async fn handle(
+    command: &str,
+    control: &Control,
+    tracker: &tracker::Buffer,
+    i2p: &RwLock<HashMap<String, I2pSession>>,
+) -> String {
+    let mut words = command.split_whitespace();
+    match (words.next(), words.next()) {
+        (Some("status"), None) => {
+            let mut b = String::new();
+            {
+                let s = control.state.lock().unwrap();
+                let _ = writeln!(b, "queue: {}/{}", s.queue_position, s.queue_total);
+                let _ = writeln!(b, "current: {}", s.current.as_deref().unwrap_or("-"));
+                let _ = writeln!(b, "banned: {}", s.banned.len());
+                for h in &s.banned {
+                    let _ = writeln!(b, "  {h}");
+                }
+            }
+            let _ = writeln!(b, "i2p sessions: {}", i2p.read().await.len());
+            let _ = writeln!(b, "trackers:");
+            for (url, h) in tracker.health() {
+                let _ = writeln!(
+                    b,
+                    "  {url} success: {} failure: {}{}",
+                    h.success,
+                    h.failure,
+                    h.last_error
+                        .map(|e| format!(" (last error: {e})"))
+                        .unwrap_or_default()
+                );
+            }
+            b
+        }
+        (Some("resolve"), Some(h)) => match Id20::from_str(h) {
+            Ok(i) => {
+                control.state.lock().unwrap().force.push(i);
+                control.wake.notify_one();
+                "ok: queued to resolve\n".into()
+            }
+            Err(e) => format!("error: invalid info-hash: {e}\n"),
+        },
+        (Some("unban"), Some(h)) => match Id20::from_str(h) {
+            Ok(i) => {
+                control.state.lock().unwrap().unban.push(i);
+                "ok: unban on the next step\n".into()
+            }
+            Err(e) => format!("error: invalid info-hash: {e}\n"),
+        },
+        (Some("skip"), None) => {
+            // store the permit, to not miss the request between the resolver steps
+            if control.state.lock().unwrap().current.is_none() {
+                return "error: no torrent is being resolved\n".into();
+            }
+            control.skip.notify_one();
+            "ok: skip requested\n".into()
+        }
+        (Some("wake"), None) => {
+            control.wake.notify_one();
+            "ok: new iteration requested\n".into()
+        }
+        _ => format!("error: unknown command `{command}`\n"),
+    }
+}
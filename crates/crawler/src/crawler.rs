@@ -0,0 +1,1196 @@
+use crate::{
+    config::{Config, timeout::Timeout, validate::Validate},
+    control::{self, Control},
+    full_scrape::{self, Entry, FullScrape},
+    hook::{Event, Hooks, Kind},
+    metrics::{self, Metrics},
+    tracker::{self, I2pSession, Tracker},
+};
+use anyhow::{Result, anyhow, bail};
+use btpeer::http::query::Scrape;
+use btracker_fs::{
+    crawler::{
+        Storage,
+        failure::Failures,
+        filter::Filter,
+        lease::Leases,
+        lifecycle::{Expire, Policy},
+        spam,
+        validate::{Limits, info_hash, validate},
+    },
+    history::{History, Sample},
+    index::Swarm,
+    layout::Layout,
+    meta::{Meta, Outcome, Preload},
+};
+use chrono::{DateTime, Local, Utc};
+use librqbit::{
+    AddTorrent, AddTorrentOptions, AddTorrentResponse, ConnectionOptions, Session, SessionOptions,
+    dht::Id20, limits::LimitsConfig,
+};
+use log::*;
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    net::SocketAddr,
+    num::NonZero,
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, atomic::Ordering},
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::{RwLock, broadcast},
+    task::{self, JoinHandle},
+    time,
+};
+use url::Url;
+
+/// Max events to keep for the slow [Crawler::events] receivers
+const EVENTS_CAPACITY: usize = 1000;
+
+/// Min metadata index journal entries to merge them into the snapshot
+const INDEX_JOURNAL_CAPACITY: usize = 1000;
+
+/// Min seconds between the disk usage updates of the metrics
+const DISK_USAGE_INTERVAL: i64 = 3600;
+
+/// Result of the info-hash handle
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Handled {
+    /// Torrent is committed
+    Resolved,
+    /// Torrent is committed before
+    Exists,
+    /// Info-hash is rejected, skipped by policy or banned before
+    Skipped,
+    /// Could not resolve the info-hash (banned temporarily)
+    Failed,
+}
+
+impl std::fmt::Display for Handled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Resolved => "resolved",
+                Self::Exists => "exists",
+                Self::Skipped => "skipped",
+                Self::Failed => "failed",
+            }
+        )
+    }
+}
+
+/// Crawler settings
+pub struct Options {
+    /// Estimated info-hash index capacity
+    pub info_hash_capacity: usize,
+    /// Crawl loop delay in seconds
+    pub sleep_seconds: u64,
+    pub timeout: Timeout,
+    /// Bind librqbit session on specified device name (`tun0`, `mycelium`, etc.)
+    pub bind_device_name: Option<String>,
+    /// Limit download speed (b/s)
+    pub download_limit: Option<u32>,
+    /// Extend the peers discovered by the trackers
+    pub initial_peers: Option<Vec<SocketAddr>>,
+    /// Use `socks5://[username:password@]host:port` for librqbit connections
+    pub proxy_url: Option<Url>,
+    /// The P2P Blocklist file URL (to filter outgoing connections)
+    pub blocklist_url: Option<Url>,
+    /// Min interval between the swarm stats samples of the same info-hash
+    pub history_interval_seconds: u64,
+    /// Keep every swarm stats sample for given time, downsample to the daily max after
+    pub history_raw_seconds: u64,
+    /// Remove swarm stats samples older than given time
+    pub history_retention_seconds: u64,
+    /// Apply the retention policy not more often than given interval
+    pub lifecycle_interval_seconds: u64,
+    /// Listen for the admin commands on given Unix socket path (in `run` mode)
+    pub control_socket: Option<PathBuf>,
+    /// Serve Prometheus metrics on given address (in `run` mode)
+    pub metrics_listen: Option<SocketAddr>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            info_hash_capacity: 1000,
+            sleep_seconds: 60,
+            timeout: Timeout::default(),
+            bind_device_name: None,
+            download_limit: None,
+            initial_peers: None,
+            proxy_url: None,
+            blocklist_url: None,
+            history_interval_seconds: 3600,
+            history_raw_seconds: 7 * 86400,
+            history_retention_seconds: 365 * 86400,
+            lifecycle_interval_seconds: 3600,
+            control_socket: None,
+            metrics_listen: None,
+        }
+    }
+}
+
+/// Build the [Crawler] from the separated components
+///
+/// * the [Storage] is required, other components are optional
+pub struct Builder {
+    storage: Storage,
+    sources: Vec<FullScrape>,
+    trackers: Vec<Tracker>,
+    virtual_peers: Arc<RwLock<HashMap<String, I2pSession>>>,
+    limits: Limits,
+    failures: Option<Failures>,
+    filter: Filter,
+    history: Option<History>,
+    lifecycle: Option<Policy>,
+    hooks: Option<Hooks>,
+    options: Options,
+}
+
+impl Builder {
+    /// Init builder for given `storage`, with the default settings
+    pub fn new(storage: Storage) -> Self {
+        let v = Validate::default();
+        Self {
+            storage,
+            sources: Vec::new(),
+            trackers: Vec::new(),
+            virtual_peers: Arc::new(RwLock::new(HashMap::new())),
+            limits: Limits {
+                max_files: v.max_files,
+                max_size: v.max_metadata_size,
+            },
+            failures: None,
+            filter: Filter::default(),
+            history: None,
+            lifecycle: None,
+            hooks: None,
+            options: Options::default(),
+        }
+    }
+
+    /// Add the info-hash source
+    pub fn source(mut self, source: FullScrape) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    /// Add the peers source
+    ///
+    /// * use [Builder::virtual_peers] to init the [Tracker::I2p]
+    pub fn tracker(mut self, tracker: Tracker) -> Self {
+        self.trackers.push(tracker);
+        self
+    }
+
+    /// Metadata sanity limits
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Persistent list of rejected info-hashes (in the storage root by default)
+    pub fn failures(mut self, failures: Failures) -> Self {
+        self.failures = Some(failures);
+        self
+    }
+
+    /// Policy to skip unwanted torrents
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Record the swarm stats of the listed info-hashes
+    pub fn history(mut self, history: History) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Retention policy for delisted or dead torrents
+    pub fn lifecycle(mut self, policy: Policy) -> Self {
+        self.lifecycle = Some(policy);
+        self
+    }
+
+    /// Deliver the crawler events to the external handlers
+    pub fn hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Session and loop settings
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Virtual sockets index shared with the I2P trackers
+    pub fn virtual_peers(&self) -> Arc<RwLock<HashMap<String, I2pSession>>> {
+        self.virtual_peers.clone()
+    }
+
+    /// Init the [Crawler], create the default failures index if not set
+    pub fn build(self) -> Result<Crawler> {
+        Ok(Crawler {
+            failures: match self.failures {
+                Some(f) => f,
+                None => Failures::init(self.storage.root(), false)?,
+            },
+            ban: HashSet::with_capacity(self.options.info_hash_capacity),
+            skipped: HashSet::new(),
+            first_seen: HashMap::with_capacity(self.options.info_hash_capacity),
+            swarm: HashMap::with_capacity(self.options.info_hash_capacity),
+            full_scrape: full_scrape::Buffer(self.sources),
+            tracker: Arc::new(tracker::Buffer::new(self.trackers)),
+            control: Arc::new(Control::default()),
+            metrics: Arc::new(Metrics::default()),
+            disk_usage: None,
+            events: broadcast::channel(EVENTS_CAPACITY).0,
+            history_compacted: None,
+            lifecycle_checked: None,
+            preload: self.storage,
+            history: self.history,
+            limits: self.limits,
+            filter: self.filter,
+            hooks: self.hooks,
+            lifecycle: self.lifecycle,
+            virtual_peers: self.virtual_peers,
+            options: self.options,
+            time_init: Local::now(),
+        })
+    }
+}
+
+pub struct Crawler {
+    options: Options,
+    preload: Storage,
+    history: Option<History>,
+    history_compacted: Option<DateTime<Utc>>,
+    limits: Limits,
+    failures: Failures,
+    filter: Filter,
+    hooks: Option<Hooks>,
+    events: broadcast::Sender<Event>,
+    lifecycle: Option<Policy>,
+    lifecycle_checked: Option<DateTime<Utc>>,
+    full_scrape: full_scrape::Buffer,
+    tracker: Arc<tracker::Buffer>,
+    virtual_peers: Arc<RwLock<HashMap<String, I2pSession>>>,
+    control: Arc<Control>,
+    metrics: Arc<Metrics>,
+    /// Last disk usage walk for the metrics, started at given time
+    disk_usage: Option<(DateTime<Utc>, JoinHandle<()>)>,
+    /// Ban list to skip unresolvable info-hashes between the queue iterations
+    ban: HashSet<Id20>,
+    /// Info-hashes skipped by the filter policy, kept in memory only
+    /// to evaluate them again with the actual policy after restart
+    skipped: HashSet<String>,
+    /// Remember the time when info-hash was listed first (for the metadata sidecar)
+    first_seen: HashMap<Id20, DateTime<Utc>>,
+    /// Last swarm stats written to the metadata index
+    swarm: HashMap<Id20, Swarm>,
+    time_init: DateTime<Local>,
+}
+
+impl Crawler {
+    // Constructors
+
+    /// Start building the crawler for given `storage`
+    pub fn builder(storage: Storage) -> Builder {
+        Builder::new(storage)
+    }
+
+    /// Init the crawler components from `config`
+    /// * returns error on the invalid values (e.g. regex syntax or unsupported tracker scheme)
+    pub async fn from_config(config: Config) -> Result<Self> {
+        let mut preload = Storage::init(
+            config.preload.path,
+            config.preload.content_path,
+            config.preload.regex.map(|r| Regex::new(&r)).transpose()?,
+            config.preload.max_filecount,
+            config.preload.max_filesize,
+            config.preload.blobs,
+            if config.preload.sharded {
+                Layout::Sharded
+            } else {
+                Layout::Flat
+            },
+        )
+        .map_err(|e| anyhow!("could not init storage: {e}"))?;
+
+        // coordinate with other workers sharing the storage, if enabled
+        if config.worker.enabled {
+            if config.worker.lease_seconds
+                <= config
+                    .timeout
+                    .add_torrent_seconds
+                    .max(config.timeout.torrent_preload_seconds)
+            {
+                warn!("lease time is less than the resolve timeouts, the work may be duplicated.")
+            }
+            preload.leases = Some(Leases::init(
+                preload.root(),
+                config.worker.name.unwrap_or_else(worker_name),
+                Duration::from_secs(config.worker.lease_seconds),
+            )?)
+        }
+
+        // init the persistent list of rejected info-hashes
+        let failures = Failures::init(preload.root(), config.validate.quarantine)?;
+
+        // init swarm stats history, if enabled
+        let history = if config.history.enabled {
+            Some(History::init(preload.root())?)
+        } else {
+            None
+        };
+
+        let mut builder = Builder::new(preload)
+            .failures(failures)
+            .limits(Limits {
+                max_files: config.validate.max_files,
+                max_size: config.validate.max_metadata_size,
+            })
+            .filter(Filter {
+                skip_private: config.filter.skip_private,
+                name_deny: config
+                    .filter
+                    .name_deny
+                    .map(|r| Regex::new(&r))
+                    .transpose()?,
+                file_deny: config
+                    .filter
+                    .file_deny
+                    .map(|r| Regex::new(&r))
+                    .transpose()?,
+                min_size: config.filter.min_size,
+                max_size: config.filter.max_size,
+                extensions: config
+                    .filter
+                    .extensions
+                    .into_iter()
+                    .map(|e| e.trim_start_matches('.').to_lowercase())
+                    .collect(),
+            });
+
+        if let Some(h) = history {
+            builder = builder.history(h)
+        }
+
+        // init event hooks, if configured
+        if let Some(hook) = config.hook {
+            builder = builder.hooks(Hooks::init(hook)?)
+        }
+
+        // init retention policy, if enabled
+        if config.lifecycle.enabled {
+            builder = builder.lifecycle(Policy {
+                drop_content: config.lifecycle.drop_content_days.map(|d| d * 86400),
+                expire: config.lifecycle.expire_days.map(|d| {
+                    (
+                        d * 86400,
+                        match config.lifecycle.archive {
+                            Some(a) => Expire::Archive(a),
+                            None => Expire::Delete,
+                        },
+                    )
+                }),
+            })
+        }
+
+        // init info-hash sources
+        for i in config.tracker.scrape {
+            if !i.url.scheme().starts_with("http") {
+                bail!("unsupported tracker `{}`, HTTP trackers only", i.url)
+            }
+            info!(
+                "init full-scrape source {}, proxy: {:?}",
+                i.url, i.proxy_url
+            );
+            builder = builder.source(FullScrape {
+                name: i.name.unwrap_or_else(|| i.url.to_string()),
+                proxy: i.proxy_url,
+                query: Scrape::new(i.url.as_str(), None)?,
+                timeout: Duration::from_secs(i.timeout),
+                url: i.url,
+            })
+        }
+
+        // init trackers (for DHT data preload)
+        for i in config.tracker.announce {
+            if !i.url.scheme().starts_with("http") {
+                bail!("unsupported tracker `{}`, HTTP trackers only", i.url)
+            }
+            info!("init tracker {}, proxy: {:?}", i.url, i.proxy_url);
+            builder = builder.tracker(Tracker::Default {
+                proxy: i.proxy_url,
+                timeout: Duration::from_secs(i.timeout),
+                url: i.url,
+                port: i.port,
+                peers_limit: i.peers_limit,
+            })
+        }
+
+        // init I2P trackers, if exists
+        if let Some(a) = config.tracker.announce_i2p {
+            use yosemite::{Session, SessionOptions};
+            for i in a {
+                if !i.url.scheme().starts_with("http") {
+                    bail!("unsupported I2P tracker `{}`, HTTP trackers only", i.url)
+                }
+                info!("init I2P tracker {}, proxy: {:?}", i.url, i.proxy_url);
+                let peers_map = builder.virtual_peers();
+                builder = builder.tracker(Tracker::I2p {
+                    loopback: i.loopback_host,
+                    proxy: i.proxy_url,
+                    announce_timeout: Duration::from_secs(i.timeout),
+                    peer_connect_timeout: Duration::from_secs(
+                        config.timeout.i2p_peer_connect_timeout_seconds,
+                    ),
+                    url: i.url,
+                    port: i.port,
+                    peers_limit: i.peers_limit,
+                    sam_session: Arc::new(RwLock::new(
+                        Session::new(SessionOptions {
+                            inbound_len: i.inbound_len,
+                            outbound_len: i.outbound_len,
+                            ..SessionOptions::default()
+                        })
+                        .await?,
+                    )),
+                    peers_map,
+                })
+            }
+        }
+
+        builder
+            .options(Options {
+                info_hash_capacity: config.info_hash_capacity,
+                sleep_seconds: config.sleep_seconds,
+                timeout: config.timeout,
+                bind_device_name: config.bind_device_name,
+                download_limit: config.download_limit,
+                initial_peers: config.initial_peers,
+                proxy_url: config.proxy_url,
+                blocklist_url: config.blocklist_url,
+                history_interval_seconds: config.history.interval_seconds,
+                history_raw_seconds: config.history.raw_days * 86400,
+                history_retention_seconds: config.history.retention_days * 86400,
+                lifecycle_interval_seconds: config.lifecycle.interval_seconds,
+                control_socket: config.control_socket,
+                metrics_listen: config.metrics_listen,
+            })
+            .build()
+    }
+
+    // Getters
+
+    /// Subscribe to the resolutions and failures
+    ///
+    /// * slow receivers lose the oldest events, see [broadcast::error::RecvError::Lagged]
+    pub fn events(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    // Actions
+
+    /// Crawl the sources in the endless loop
+    ///
+    /// * starts the control and metrics listeners, if configured
+    pub async fn run(&mut self) -> Result<()> {
+        // init admin control interface, if enabled
+        if let Some(ref socket) = self.options.control_socket {
+            control::listen(
+                socket,
+                self.control.clone(),
+                self.tracker.clone(),
+                self.virtual_peers.clone(),
+            )?
+        }
+        // init Prometheus exporter, if enabled
+        if let Some(address) = self.options.metrics_listen {
+            metrics::listen(
+                address,
+                self.metrics.clone(),
+                self.tracker.clone(),
+                self.virtual_peers.clone(),
+            )
+            .await?
+        }
+        info!("crawler started");
+        loop {
+            self.run_once().await?;
+            tokio::select! {
+                _ = time::sleep(Duration::from_secs(self.options.sleep_seconds)) => (),
+                _ = self.control.wake.notified() => debug!("new iteration requested by the control."),
+            }
+        }
+    }
+
+    /// Crawl the sources queue once
+    pub async fn run_once(&mut self) -> Result<()> {
+        let time_queue = Local::now();
+        debug!("queue crawl begin...");
+
+        // Cleanup inactive I2P sessions if exists
+        let cleanup = self.options.timeout.cleanup_inactive_i2p_session_seconds;
+        self.virtual_peers.write().await.retain(|b32, s| {
+            if (Utc::now().timestamp() as u64).saturating_sub(s.last_active.load(Ordering::Relaxed))
+                > cleanup
+            {
+                debug!(
+                    "I2P session {b32} is inactive; aborting handler on {}",
+                    s.socket
+                );
+                s.handler.abort();
+                false
+            } else {
+                true
+            }
+        });
+
+        let session = self.session().await?;
+
+        // release the info-hashes locked by the interrupted workers
+        if let Some(ref l) = self.preload.leases {
+            let removed = l.gc()?;
+            if removed > 0 {
+                debug!("removed {removed} expired leases.")
+            }
+        }
+
+        // merge the metadata index journal, by one of the workers sharing the storage
+        if self.preload.index().journal()? >= INDEX_JOURNAL_CAPACITY
+            && let Some(_lease) = self.preload.lease("index")?
+        {
+            let merged = self.preload.index().compact()?;
+            debug!("merged {merged} metadata index journal entries.")
+        }
+
+        // build unique ID index from the multiple info-hash sources
+        let (queue, failed) = self
+            .full_scrape
+            .get(self.options.info_hash_capacity)
+            .await?;
+        // info-hashes requested by the control interface, resolve them first
+        let force = self.control.take_force();
+        // clean up nonexistent ban entries from the memory pool
+        self.ban.retain(|i| {
+            let is_retain = queue.contains_key(i);
+            if !is_retain {
+                debug!(
+                    "remove {} from the ban list, as it is no longer available in the source.",
+                    i.as_string()
+                )
+            }
+            is_retain
+        });
+
+        debug!(
+            "fetched {} unique hashes, banned: {}.",
+            queue.len(),
+            self.ban.len()
+        );
+
+        // count info-hashes listed by every source
+        let mut sources = BTreeMap::new();
+        for e in queue.values() {
+            for s in &e.sources {
+                *sources.entry(s.clone()).or_default() += 1
+            }
+        }
+        self.metrics.sources(sources);
+
+        self.first_seen.retain(|i, _| queue.contains_key(i));
+        for i in queue.keys() {
+            self.first_seen.entry(*i).or_insert(Utc::now());
+        }
+
+        // update the swarm stats of the metadata index on change, by one of the workers
+        // * skip on empty queue, to keep the last stats when the sources are unavailable
+        if !queue.is_empty() {
+            let swarm: HashMap<Id20, Swarm> = queue
+                .iter()
+                .map(|(i, e)| {
+                    (
+                        *i,
+                        Swarm {
+                            seeders: e.seeders,
+                            leechers: e.leechers,
+                        },
+                    )
+                })
+                .collect();
+            if swarm != self.swarm
+                && let Some(_lease) = self.preload.lease("swarm")?
+            {
+                self.preload
+                    .index()
+                    .write_swarm(swarm.iter().map(|(i, s)| (i.as_string(), *s)))?;
+                debug!("updated swarm stats for {} hashes.", swarm.len());
+                self.swarm = swarm
+            }
+        }
+
+        // record the swarm stats, compact outdated records once a day
+        if let Some(ref h) = self.history {
+            let now = Utc::now();
+            let mut recorded = 0;
+            for (i, e) in queue.iter() {
+                if h.push(
+                    &i.as_string(),
+                    Sample {
+                        time: now,
+                        seeders: e.seeders,
+                        leechers: e.leechers,
+                        downloaded: e.downloaded,
+                    },
+                    self.options.history_interval_seconds,
+                )? {
+                    recorded += 1
+                }
+            }
+            debug!("recorded swarm stats history for {recorded} hashes.");
+            // with the lease of the lifecycle pass, to not rewrite the files of another worker
+            if self
+                .history_compacted
+                .is_none_or(|t| (now - t).num_days() > 0)
+            {
+                if let Some(_lease) = self.preload.lease("lifecycle")? {
+                    h.compact(
+                        self.options.history_raw_seconds,
+                        self.options.history_retention_seconds,
+                    )?;
+                    self.history_compacted = Some(now);
+                    debug!("swarm stats history compacted.")
+                } else {
+                    debug!("history is compacted by another worker, skip.")
+                }
+            }
+        }
+
+        // apply the retention policy to the committed torrents
+        // * skip on empty queue, to not mark everything delisted when the sources are unavailable
+        // * skip if any source failed, to not mark delisted the torrents listed by it only
+        if let Some(ref policy) = self.lifecycle
+            && !queue.is_empty()
+            && self.lifecycle_checked.is_none_or(|t| {
+                (Utc::now() - t).num_seconds() as u64 >= self.options.lifecycle_interval_seconds
+            })
+        {
+            if !failed.is_empty() {
+                // retry on the next iteration
+                warn!(
+                    "full-scrape of {} failed, skip lifecycle update.",
+                    failed.join(", ")
+                )
+            } else {
+                // by one of the workers sharing the storage
+                if let Some(_lease) = self.preload.lease("lifecycle")? {
+                    for (h, event) in self.preload.lifecycle(
+                        |h| {
+                            Id20::from_str(h)
+                                .ok()
+                                .and_then(|i| queue.get(&i))
+                                .map(|e| e.seeders)
+                        },
+                        policy,
+                    )? {
+                        info!("torrent {h} {event}.");
+                        self.emit(Event::new(event.into(), &h));
+                    }
+                } else {
+                    debug!("lifecycle is updated by another worker, skip.")
+                }
+                self.lifecycle_checked = Some(Utc::now())
+            }
+        }
+
+        // handle
+        self.control.state.lock().unwrap().queue_total = force.len() + queue.len();
+        for (n, (i, entry, is_forced)) in force
+            .into_iter()
+            .map(|i| (i, Entry::default(), true))
+            .chain(queue.into_iter().map(|(i, e)| (i, e, false)))
+            .enumerate()
+        {
+            // apply control requests
+            for u in self.control.take_unban() {
+                if self.ban.remove(&u) {
+                    info!("torrent {} unbanned by the control request.", u.as_string())
+                }
+            }
+            self.control.banned(&self.ban);
+            self.control.position(n + 1, Some(i.as_string()));
+
+            self.handle(&session, i, entry, is_forced).await?;
+        }
+        session.stop().await;
+        let total = self.control.state.lock().unwrap().queue_total;
+        self.control.position(total, None);
+        self.control.banned(&self.ban);
+        self.metrics.iteration(
+            Local::now()
+                .signed_duration_since(time_queue)
+                .as_seconds_f64(),
+        );
+        // walk the storage in background, at most once in `DISK_USAGE_INTERVAL` seconds
+        if self.options.metrics_listen.is_some()
+            && self.disk_usage.as_ref().is_none_or(|(t, h)| {
+                h.is_finished() && (Utc::now() - *t).num_seconds() >= DISK_USAGE_INTERVAL
+            })
+        {
+            let paths = vec![self.preload.root().clone(), self.preload.content().clone()];
+            let m = self.metrics.clone();
+            self.disk_usage = Some((
+                Utc::now(),
+                task::spawn_blocking(move || m.disk_usage(metrics::disk_usage(&paths))),
+            ))
+        }
+        debug!(
+            "queue completed at {time_queue} (time: {} / uptime: {} / banned: {}) await {} seconds to continue...",
+            Local::now()
+                .signed_duration_since(time_queue)
+                .as_seconds_f32(),
+            Local::now()
+                .signed_duration_since(self.time_init)
+                .as_seconds_f32(),
+            self.ban.len(),
+            self.options.sleep_seconds
+        );
+        Ok(())
+    }
+
+    /// Resolve single info-hash with the configured trackers and preload rules,
+    /// even if it was rejected or banned before
+    pub async fn resolve(&mut self, info_hash: Id20) -> Result<Handled> {
+        let session = self.session().await?;
+        let handled = self
+            .handle(&session, info_hash, Entry::default(), true)
+            .await;
+        session.stop().await;
+        handled
+    }
+
+    /// Commit local `.torrent` file bytes
+    ///
+    /// * the metadata is validated and filtered as received from the peers
+    pub fn import(&mut self, torrent_bytes: Vec<u8>) -> Result<Handled> {
+        let h = match info_hash(&torrent_bytes) {
+            Ok(h) => h,
+            Err(reason) => {
+                warn!("could not import torrent: {reason}");
+                return Ok(Handled::Skipped);
+            }
+        };
+        let Some(_lease) = self.preload.lease(&h)? else {
+            debug!("torrent {h} is handled by another worker, skip.");
+            return Ok(Handled::Skipped);
+        };
+        if self.preload.contains_torrent(&h)? {
+            debug!("torrent {h} exists, skip.");
+            return Ok(Handled::Exists);
+        }
+        if !self.check(&h, &torrent_bytes, false)? {
+            return Ok(Handled::Skipped);
+        }
+        let meta = Meta {
+            spam: Some(spam::score(&torrent_bytes)),
+            ..Meta::default()
+        };
+        let c = self.preload.commit(&h, torrent_bytes, None, meta)?;
+        info!("torrent {h} imported.");
+        self.emit(Event {
+            name: c.name,
+            paths: vec![c.torrent],
+            ..Event::new(Kind::Resolved, &h)
+        });
+        Ok(Handled::Resolved)
+    }
+
+    /// Build unique ID index from the multiple info-hash sources
+    pub async fn sources(&self) -> Result<HashMap<Id20, Entry>> {
+        Ok(self
+            .full_scrape
+            .get(self.options.info_hash_capacity)
+            .await?
+            .0)
+    }
+
+    /// Deliver the pending hook events and stop
+    pub async fn shutdown(self) {
+        if let Some(hooks) = self.hooks {
+            hooks.shutdown().await
+        }
+    }
+
+    // Helpers
+
+    /// Send the `event` to the subscribers and hooks
+    fn emit(&self, event: Event) {
+        if let Some(ref hooks) = self.hooks {
+            hooks.emit(event.clone())
+        }
+        let _ = self.events.send(event); // no subscribers
+    }
+
+    async fn session(&self) -> Result<Arc<Session>> {
+        // Please, note:
+        // * it's important to start new `Session` inside the crawler loop:
+        //   https://github.com/ikatson/rqbit/issues/481
+        // * when fix and after starting it once (outside the loop),
+        //   remove also each torrent after resolve it with `session.delete`, to prevent impl panic (see `single-session` branch)
+        Session::new_with_opts(
+            self.preload.root().clone(),
+            SessionOptions {
+                bind_device_name: self.options.bind_device_name.clone(),
+                blocklist_url: self.options.blocklist_url.as_ref().map(|b| b.to_string()),
+                listen: None,
+                connect: Some(ConnectionOptions {
+                    proxy_url: self.options.proxy_url.as_ref().map(|u| u.to_string()),
+                    ..ConnectionOptions::default()
+                }),
+                dht: None,
+                disable_local_service_discovery: true,
+                disable_upload: true,
+                fastresume: false,
+                persistence: None,
+                ratelimits: LimitsConfig {
+                    download_bps: self.options.download_limit.and_then(NonZero::new),
+                    ..LimitsConfig::default()
+                },
+                trackers: HashSet::new(), // we're resolving peers manually
+                ..SessionOptions::default()
+            },
+        )
+        .await
+    }
+
+    /// Validate and filter received metadata, remember the rejected info-hash
+    ///
+    /// Returns `true` if the torrent can be committed
+    fn check(&mut self, h: &str, torrent_bytes: &[u8], is_discard: bool) -> Result<bool> {
+        if let Err(reason) = validate(h, torrent_bytes, &self.limits) {
+            warn!("torrent {h} rejected: {reason}");
+            self.metrics.failed("invalid");
+            self.failures.insert(h, &reason, Some(torrent_bytes))?;
+            if is_discard {
+                self.preload.discard(h)?;
+            }
+            self.emit(Event::new(Kind::Rejected, h));
+            return Ok(false);
+        }
+        if let Err(reason) = self.filter.check(torrent_bytes) {
+            debug!("torrent {h} skipped by policy: {reason}");
+            self.metrics.failed("policy");
+            self.skipped.insert(h.to_string());
+            if is_discard {
+                self.preload.discard(h)?;
+            }
+            self.emit(Event::new(Kind::Skipped, h));
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Resolve the info-hash `i` in the `session`
+    ///
+    /// * `is_forced` to ignore the rejected and banned state
+    async fn handle(
+        &mut self,
+        session: &Arc<Session>,
+        i: Id20,
+        entry: Entry,
+        is_forced: bool,
+    ) -> Result<Handled> {
+        // convert to string once
+        let h = i.as_string();
+        if self.preload.contains_torrent(&h)? {
+            // keep the provenance of the committed torrent actual
+            if self.preload.add_sources(&h, &entry.sources)? {
+                debug!("torrent {h} exists, sources updated.")
+            } else {
+                debug!("torrent {h} exists, skip.")
+            }
+            return Ok(Handled::Exists);
+        }
+
+        // skip rejected entry permanently
+        if !is_forced && let Some(reason) = self.failures.get(&h) {
+            debug!("torrent {h} is rejected ({reason}), skip.");
+            return Ok(Handled::Skipped);
+        }
+
+        // skip entry unwanted by the current filter policy
+        if !is_forced && self.skipped.contains(&h) {
+            debug!("torrent {h} is skipped by policy, skip.");
+            return Ok(Handled::Skipped);
+        }
+
+        // skip banned entry, remove it from the ban list to retry on the next iteration
+        if self.ban.remove(&i) && !is_forced {
+            debug!("torrent {h} is banned, skip.");
+            return Ok(Handled::Skipped);
+        }
+
+        // lock the info-hash for this worker, as the storage may be shared
+        let Some(lease) = self.preload.lease(&h)? else {
+            debug!("torrent {h} is handled by another worker, skip.");
+            return Ok(Handled::Skipped);
+        };
+        // committed by another worker since the check above
+        if self.preload.contains_torrent(&h)? {
+            debug!("torrent {h} exists, skip.");
+            return Ok(Handled::Exists);
+        }
+
+        debug!("resolve {h}...");
+        let time_resolve = Instant::now();
+
+        // discover unique peers first
+        let initial_peers = match self.tracker.peers(&i).await {
+            Ok(mut peers) => {
+                if let Some(ref p) = self.options.initial_peers {
+                    debug!("forcefully extend with {} peers ({p:?})", p.len());
+                    peers.extend(p);
+                }
+                if peers.is_empty() {
+                    debug!("could not find peers for torrent {h}, skip.");
+                    self.metrics.failed("no_peers");
+                    return Ok(Handled::Failed);
+                } else {
+                    let l = peers.len();
+                    debug!("collected {l} peers for torrent {h}.");
+                    peers
+                }
+            }
+            Err(e) => {
+                warn!("could not get peers for torrent {h}: {e}, skip.");
+                self.metrics.failed("tracker");
+                return Ok(Handled::Failed);
+            }
+        };
+
+        // make sure the list is not empty as unexpected here
+        assert!(!initial_peers.is_empty());
+
+        // init metadata sidecar
+        let mut meta = Meta {
+            first_seen: self.first_seen.get(&i).copied(),
+            sources: entry.sources,
+            trackers: self.tracker.urls(),
+            peers: initial_peers.len(),
+            ..Meta::default()
+        };
+
+        // run the crawler in single thread for performance reasons,
+        // use `timeout` argument option to skip the dead connections.
+        let added = tokio::select! {
+            r = time::timeout(
+                Duration::from_secs(self.options.timeout.add_torrent_seconds),
+                session.add_torrent(
+                    AddTorrent::from_url(self.tracker.magnet(&h)),
+                    Some(AddTorrentOptions {
+                        paused: true, // continue after `only_files` update
+                        overwrite: true,
+                        disable_trackers: true, // we're resolving peers manually
+                        initial_peers: Some(initial_peers.into_iter().collect()),
+                        list_only: self.preload.regex.is_none(),
+                        // the destination folder to preload files match `preload_regex`
+                        // * e.g. images for audio albums
+                        output_folder: self.preload.tmp_dir(&h, true)?.to_str().map(|s| s.to_string()),
+                        ..Default::default()
+                    }),
+                ),
+            ) => r,
+            _ = self.control.skip.notified() => {
+                info!("torrent {h} skipped by the control request.");
+                self.metrics.failed("control");
+                self.preload.discard(&h)?;
+                return Ok(Handled::Failed);
+            }
+        };
+        match added {
+            Ok(r) => match r {
+                Ok(AddTorrentResponse::ListOnly(l)) => {
+                    assert!(self.preload.regex.is_none());
+                    if !self.check(&h, &l.torrent_bytes, true)? {
+                        return Ok(Handled::Skipped);
+                    }
+                    meta.spam = Some(spam::score(&l.torrent_bytes));
+                    // keep the tmp directory, as it belongs to the worker holding the lease now
+                    if !lease.is_held()? {
+                        warn!("lease of torrent {h} is lost, skip.");
+                        self.metrics.failed("lease");
+                        return Ok(Handled::Failed);
+                    }
+                    debug!("persist bytes for torrent file {h}...");
+                    let c = self
+                        .preload
+                        .commit(&h, l.torrent_bytes.to_vec(), None, meta)?;
+                    info!("torrent {h} resolved.");
+                    self.metrics.resolved(time_resolve.elapsed().as_secs_f64());
+                    self.emit(Event {
+                        name: c.name,
+                        paths: vec![c.torrent],
+                        ..Event::new(Kind::Resolved, &h)
+                    });
+                    Ok(Handled::Resolved)
+                }
+                Ok(AddTorrentResponse::Added(_, mt)) => {
+                    assert!(self.preload.regex.is_some());
+                    assert!(mt.is_paused());
+                    let mut keep_files =
+                        HashSet::with_capacity(self.preload.max_filecount.unwrap_or_default());
+                    let mut only_files =
+                        HashSet::with_capacity(self.preload.max_filecount.unwrap_or_default());
+                    mt.wait_until_initialized().await?;
+                    let preload = &self.preload;
+                    let bytes = mt.with_metadata(|m| {
+                        for (id, info) in m.file_infos.iter().enumerate() {
+                            let path = info.relative_filename.to_string_lossy().to_string();
+                            if preload
+                                .max_filecount
+                                .is_some_and(|limit| only_files.len() + 1 > limit)
+                            {
+                                debug!(
+                                    "file count limit ({}) reached, skip file {id} for {h} at {path}",
+                                    only_files.len(),
+                                );
+                                meta.preload.push(Preload {
+                                    path,
+                                    outcome: Outcome::SkipCount,
+                                });
+                                continue;
+                            }
+                            if preload.max_filesize.is_some_and(|limit| info.len > limit) {
+                                debug!(
+                                    "file size ({}) limit reached, skip file {id} for {h} at {path}",
+                                    info.len,
+                                );
+                                meta.preload.push(Preload {
+                                    path,
+                                    outcome: Outcome::SkipSize,
+                                });
+                                continue;
+                            }
+                            if preload.regex.as_ref().is_some_and(|r| !r.is_match(&path)) {
+                                debug!("regex filter match: skip {id} for {h} at {path}");
+                                meta.preload.push(Preload {
+                                    path,
+                                    outcome: Outcome::SkipRegex,
+                                });
+                                continue;
+                            }
+                            debug!("keep file {id} for {h} as {path}");
+                            assert!(keep_files.insert(info.relative_filename.clone()));
+                            assert!(only_files.insert(id));
+                            meta.preload.push(Preload {
+                                path,
+                                outcome: Outcome::Keep,
+                            })
+                        }
+                        m.torrent_bytes.to_vec()
+                    })?;
+                    if !self.check(&h, &bytes, true)? {
+                        return Ok(Handled::Skipped);
+                    }
+                    meta.spam = Some(spam::score(&bytes));
+                    // extend the lease for the preload time
+                    if !lease.renew()? {
+                        warn!("lease of torrent {h} is lost, skip.");
+                        self.metrics.failed("lease");
+                        return Ok(Handled::Failed);
+                    }
+                    session.update_only_files(&mt, &only_files).await?;
+                    session.unpause(&mt).await?;
+                    debug!("begin torrent {h} preload...");
+                    let time_preload = Instant::now();
+                    let completed = tokio::select! {
+                        r = time::timeout(
+                            Duration::from_secs(self.options.timeout.torrent_preload_seconds),
+                            mt.wait_until_completed(),
+                        ) => r.map_err(|e| e.to_string()),
+                        _ = self.control.skip.notified() => Err("skipped by the control request".into()),
+                    };
+                    if let Err(e) = completed {
+                        debug!("preload torrent data for {h} failed ({e}), ban temporarily.");
+                        self.preload.discard(&h)?;
+                        assert!(self.ban.insert(i));
+                        self.metrics.banned("preload");
+                        self.emit(Event::new(Kind::PreloadFailed, &h));
+                        return Ok(Handled::Failed);
+                    }
+                    debug!("torrent {h} preload completed.");
+                    let time_preload = time_preload.elapsed().as_secs_f64();
+                    // persist torrent bytes and preloaded content,
+                    // cleanup tmp (see rqbit#408)
+                    if !lease.is_held()? {
+                        warn!("lease of torrent {h} is lost, skip.");
+                        self.metrics.failed("lease");
+                        return Ok(Handled::Failed);
+                    }
+                    debug!("persist torrent {h} with {} files...", keep_files.len());
+                    let c = self.preload.commit(&h, bytes, Some(keep_files), meta)?;
+                    self.metrics.preloaded(
+                        c.files
+                            .iter()
+                            .filter_map(|f| f.metadata().ok())
+                            .map(|m| m.len())
+                            .sum(),
+                        time_preload,
+                    );
+                    info!("torrent {h} resolved.");
+                    self.metrics.resolved(time_resolve.elapsed().as_secs_f64());
+                    let mut paths = vec![c.torrent];
+                    paths.extend(c.files);
+                    self.emit(Event {
+                        name: c.name,
+                        paths,
+                        ..Event::new(Kind::Resolved, &h)
+                    });
+                    Ok(Handled::Resolved)
+                }
+                Ok(_) => unreachable!(),
+                Err(e) => {
+                    debug!("failed to resolve torrent {h}: {e}, ban temporarily.");
+                    self.preload.discard(&h)?;
+                    assert!(self.ban.insert(i));
+                    self.metrics.banned("resolve");
+                    self.emit(Event::new(Kind::Banned, &h));
+                    Ok(Handled::Failed)
+                }
+            },
+            Err(e) => {
+                debug!(
+                    "skip awaiting the completion of adding torrent {h} ({e}), ban temporarily."
+                );
+                self.preload.discard(&h)?;
+                assert!(self.ban.insert(i));
+                self.metrics.banned("timeout");
+                self.emit(Event::new(Kind::Banned, &h));
+                Ok(Handled::Failed)
+            }
+        }
+    }
+}
+
+/// Build the default worker name from the host name and the process ID
+fn worker_name() -> String {
+    format!(
+        "{}-{}",
+        std::env::var("HOSTNAME")
+            .ok()
+            .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+            .map(|h| h.trim().to_string())
+            .filter(|h| !h.is_empty())
+            .unwrap_or("localhost".into()),
+        std::process::id()
+    )
+}
@@ -2,20 +2,40 @@ use anyhow::Result;
 use btpeer::{InfoHash, http::query::Scrape};
 use librqbit::dht::Id20;
 use log::*;
-use std::{collections::HashSet, time::Duration};
+use std::{collections::HashMap, time::Duration};
 use url::Url;
 
 pub struct FullScrape {
+    /// Source name recorded to the `Entry::sources`
+    pub name: String,
     pub proxy: Option<Url>,
     pub query: Scrape,
     pub timeout: Duration,
+    pub url: Url,
+}
+
+/// Info-hash details collected from all the full-scrape sources
+#[derive(Default)]
+pub struct Entry {
+    /// Source names listed the info-hash
+    pub sources: Vec<String>,
+    pub seeders: u32,
+    pub leechers: u32,
+    pub downloaded: u32,
 }
 
 pub struct Buffer(pub Vec<FullScrape>);
 
 impl Buffer {
-    pub async fn get(&self, expected_capacity: usize) -> Result<HashSet<Id20>> {
-        let mut s = HashSet::with_capacity(expected_capacity);
+    /// Collect unique info-hashes with the source names listed them and the swarm stats sum
+    ///
+    /// Returns the collected info-hashes and the names of the sources failed to respond
+    pub async fn get(
+        &self,
+        expected_capacity: usize,
+    ) -> Result<(HashMap<Id20, Entry>, Vec<String>)> {
+        let mut s: HashMap<Id20, Entry> = HashMap::with_capacity(expected_capacity);
+        let mut failed = Vec::new();
 
         for this in self.0.iter() {
             let scrape = match btpeer::http::scrape(
@@ -31,20 +51,41 @@ impl Buffer {
                         "[full-scrape] full-scrape {} update failed: {e}; skip",
                         &this.query
                     );
+                    failed.push(this.name.clone());
                     continue; // skip without panic
                 }
             }
-            .stats
-            .into_keys();
+            .stats;
 
             let total = scrape.len();
+            let mut dropped = 0;
 
-            for i in scrape {
-                s.insert(match i {
-                    InfoHash::V1(ref b) => Id20::from_bytes(b)?,
-                });
+            for (i, stats) in scrape {
+                // the v2 (and hybrid) torrents are listed by the truncated 20 bytes hash,
+                // the full 32 bytes v2 info-hash is not supported by the resolver
+                let id = match i {
+                    InfoHash::V1(ref b) => match Id20::from_bytes(b) {
+                        Ok(id) => id,
+                        Err(e) => {
+                            debug!("[full-scrape] drop unsupported info-hash {i}: {e}");
+                            dropped += 1;
+                            continue;
+                        }
+                    },
+                };
+                let e = s.entry(id).or_default();
+                e.sources.push(this.name.clone());
+                e.seeders = e.seeders.saturating_add(stats.complete);
+                e.leechers = e.leechers.saturating_add(stats.incomplete);
+                e.downloaded = e.downloaded.saturating_add(stats.downloaded);
             }
 
+            if dropped > 0 {
+                warn!(
+                    "[full-scrape] dropped {dropped} unsupported (not 20 bytes) info-hashes from {}",
+                    this.query.0
+                )
+            }
             debug!(
                 "[full-scrape] received {total} unique hashes from {}...",
                 this.query.0
@@ -56,6 +97,6 @@ impl Buffer {
             s.len()
         );
 
-        Ok(s)
+        Ok((s, failed))
     }
 }
@@ -0,0 +1,236 @@
+use crate::config::hook::Hook as Config;
+use anyhow::{Result, bail};
+use chrono::{DateTime, Utc};
+use log::*;
+use reqwest::{Client, header::CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, time::Duration};
+use tokio::{
+    process::Command,
+    sync::mpsc::{self, Sender, error::TrySendError},
+    task::JoinHandle,
+    time,
+};
+use url::Url;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Resolved,
+    Rejected,
+    Skipped,
+    PreloadFailed,
+    Banned,
+    Delisted,
+    Dead,
+    Dropped,
+    Archived,
+    Deleted,
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Resolved => "resolved",
+                Self::Rejected => "rejected",
+                Self::Skipped => "skipped",
+                Self::PreloadFailed => "preload_failed",
+                Self::Banned => "banned",
+                Self::Delisted => "delisted",
+                Self::Dead => "dead",
+                Self::Dropped => "dropped",
+                Self::Archived => "archived",
+                Self::Deleted => "deleted",
+            }
+        )
+    }
+}
+
+impl From<btracker_fs::crawler::lifecycle::Event> for Kind {
+    fn from(event: btracker_fs::crawler::lifecycle::Event) -> Self {
+        use btracker_fs::crawler::lifecycle::Event;
+        match event {
+            Event::Delisted => Self::Delisted,
+            Event::Dead => Self::Dead,
+            Event::Dropped => Self::Dropped,
+            Event::Archived => Self::Archived,
+            Event::Deleted => Self::Deleted,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Event {
+    pub event: Kind,
+    pub info_hash: String,
+    pub name: Option<String>,
+    pub paths: Vec<PathBuf>,
+    pub time: DateTime<Utc>,
+}
+
+impl Event {
+    pub fn new(event: Kind, info_hash: &str) -> Self {
+        Self {
+            event,
+            info_hash: info_hash.to_string(),
+            name: None,
+            paths: Vec::new(),
+            time: Utc::now(),
+        }
+    }
+}
+
+/// Deliver crawler events to the external handlers in background
+///
+/// * events are dropped when the queue is full, so slow handlers never block the crawler
+pub struct Hooks {
+    events: Vec<Kind>,
+    sender: Sender<Event>,
+    worker: JoinHandle<()>,
+}
+
+impl Hooks {
+    /// Spawn the delivery worker
+    pub fn init(config: Config) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds))
+            .build()?;
+        let (sender, mut receiver) = mpsc::channel::<Event>(config.queue.get());
+        let events = config.events.clone();
+        let worker = tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                deliver(&config, &client, &event).await
+            }
+        });
+        Ok(Self {
+            events,
+            sender,
+            worker,
+        })
+    }
+
+    /// Queue the `event` for delivery (without waiting)
+    pub fn emit(&self, event: Event) {
+        if !self.events.is_empty() && !self.events.contains(&event.event) {
+            return;
+        }
+        match self.sender.try_send(event) {
+            Ok(()) => (),
+            Err(TrySendError::Full(e)) => {
+                warn!(
+                    "[hook] queue is full, skip {} event for {}",
+                    e.event, e.info_hash
+                )
+            }
+            Err(TrySendError::Closed(e)) => {
+                error!(
+                    "[hook] queue is closed, skip {} event for {}",
+                    e.event, e.info_hash
+                )
+            }
+        }
+    }
+
+    /// Close the queue and wait for the pending events delivery (e.g. before exit)
+    pub async fn shutdown(self) {
+        drop(self.sender);
+        if let Err(e) = self.worker.await {
+            error!("[hook] delivery worker failed: {e}")
+        }
+    }
+}
+
+async fn deliver(config: &Config, client: &Client, event: &Event) {
+    let timeout = Duration::from_secs(config.timeout_seconds);
+    if let Some(ref command) = config.command {
+        retry(config, event, "command", || run(command, event, timeout)).await
+    }
+    if let Some(ref url) = config.webhook {
+        match serde_json::to_vec(event) {
+            Ok(body) => retry(config, event, "webhook", || post(client, url, &body)).await,
+            Err(e) => error!("[hook] could not encode {} event: {e}", event.event),
+        }
+    }
+}
+
+async fn retry<F, Fut>(config: &Config, event: &Event, handler: &str, f: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let attempts = config.retries + 1;
+    for attempt in 1..=attempts {
+        match f().await {
+            Ok(()) => {
+                debug!(
+                    "[hook] {handler} handled {} event for {}",
+                    event.event, event.info_hash
+                );
+                return;
+            }
+            Err(e) => {
+                warn!(
+                    "[hook] {handler} failed to handle {} event for {} (attempt {attempt}): {e}",
+                    event.event, event.info_hash
+                );
+                if attempt == attempts {
+                    break;
+                }
+                time::sleep(Duration::from_secs(
+                    config.retry_delay_seconds * attempt as u64,
+                ))
+                .await
+            }
+        }
+    }
+    error!(
+        "[hook] {handler} could not handle {} event for {}, skip.",
+        event.event, event.info_hash
+    )
+}
+
+/// Run local `command` with the event details as the arguments and environment variables
+async fn run(command: &PathBuf, event: &Event, timeout: Duration) -> Result<()> {
+    let paths = event
+        .paths
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect::<Vec<String>>();
+    let status = time::timeout(
+        timeout,
+        Command::new(command)
+            .arg(event.event.to_string())
+            .arg(&event.info_hash)
+            .arg(event.name.as_deref().unwrap_or_default())
+            .args(&paths)
+            .env("BTRACKER_EVENT", event.event.to_string())
+            .env("BTRACKER_INFO_HASH", &event.info_hash)
+            .env("BTRACKER_NAME", event.name.as_deref().unwrap_or_default())
+            .env("BTRACKER_PATHS", paths.join("\n"))
+            .kill_on_drop(true)
+            .status(),
+    )
+    .await??;
+    if !status.success() {
+        bail!("command exit with {status}")
+    }
+    Ok(())
+}
+
+/// POST JSON `body` to the `url`
+pub(crate) async fn post(client: &Client, url: &Url, body: &[u8]) -> Result<()> {
+    let status = client
+        .post(url.clone())
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.to_vec())
+        .send()
+        .await?
+        .status();
+    if !status.is_success() {
+        bail!("webhook response status {status}")
+    }
+    Ok(())
+}
@@ -0,0 +1,14 @@
+//! SSD-friendly FS crawler of BEP 48 / Full Scrape, based on librqbit
+//!
+//! * use [Crawler::builder] to embed the crawler into own service,
+//!   or [Crawler::from_config] to init it from the `btracker-crawler` config file
+
+pub mod config;
+pub mod control;
+mod crawler;
+pub mod full_scrape;
+pub mod hook;
+pub mod metrics;
+pub mod tracker;
+
+pub use crawler::{Builder, Crawler, Handled, Options};
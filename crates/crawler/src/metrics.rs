@@ -0,0 +1,329 @@
+use crate::tracker::{self, I2pSession};
+use log::*;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write as _,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    sync::RwLock,
+};
+
+/// Upper bounds of the latency histogram buckets, in seconds
+const BUCKETS: [f64; 10] = [0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0];
+
+#[derive(Default)]
+struct Histogram {
+    /// Cumulative counts per `BUCKETS` item
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (b, le) in self.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= le {
+                *b += 1
+            }
+        }
+        self.count += 1;
+        self.sum += seconds
+    }
+
+    fn write(&self, b: &mut String, name: &str, help: &str) {
+        let _ = writeln!(b, "# HELP {name} {help}");
+        let _ = writeln!(b, "# TYPE {name} histogram");
+        for (c, le) in self.buckets.iter().zip(BUCKETS) {
+            let _ = writeln!(b, "{name}_bucket{{le=\"{le}\"}} {c}");
+        }
+        let _ = writeln!(b, "{name}_bucket{{le=\"+Inf\"}} {}", self.count);
+        let _ = writeln!(b, "{name}_sum {}", self.sum);
+        let _ = writeln!(b, "{name}_count {}", self.count);
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Info-hashes listed by the full-scrape source, on the last iteration
+    sources: BTreeMap<String, usize>,
+    resolved: u64,
+    failed: BTreeMap<&'static str, u64>,
+    banned: BTreeMap<&'static str, u64>,
+    resolve_seconds: Histogram,
+    preload_seconds: Histogram,
+    preload_bytes: u64,
+    disk_usage: Option<u64>,
+    iterations: u64,
+    iteration_seconds: f64,
+}
+
+/// Crawler counters for the Prometheus exporter
+#[derive(Default)]
+pub struct Metrics(Mutex<Inner>);
+
+impl Metrics {
+    /// Update info-hashes count listed by every full-scrape source
+    pub fn sources(&self, sources: BTreeMap<String, usize>) {
+        self.0.lock().unwrap().sources = sources
+    }
+
+    /// Count resolved torrent with the time spent from the peers lookup to commit
+    pub fn resolved(&self, seconds: f64) {
+        let mut m = self.0.lock().unwrap();
+        m.resolved += 1;
+        m.resolve_seconds.observe(seconds)
+    }
+
+    /// Count info-hash skipped by the `reason`
+    pub fn failed(&self, reason: &'static str) {
+        *self.0.lock().unwrap().failed.entry(reason).or_default() += 1
+    }
+
+    /// Count info-hash banned (temporarily) by the `reason`
+    pub fn banned(&self, reason: &'static str) {
+        *self.0.lock().unwrap().banned.entry(reason).or_default() += 1
+    }
+
+    /// Count completed content preload
+    pub fn preloaded(&self, bytes: u64, seconds: f64) {
+        let mut m = self.0.lock().unwrap();
+        m.preload_bytes += bytes;
+        m.preload_seconds.observe(seconds)
+    }
+
+    /// Count completed queue iteration
+    pub fn iteration(&self, seconds: f64) {
+        let mut m = self.0.lock().unwrap();
+        m.iterations += 1;
+        m.iteration_seconds = seconds
+    }
+
+    /// Update the disk usage of the preload and content roots
+    pub fn disk_usage(&self, bytes: u64) {
+        self.0.lock().unwrap().disk_usage = Some(bytes)
+    }
+
+    /// Build the text exposition format
+    fn render(&self, tracker: &tracker::Buffer, i2p_sessions: usize) -> String {
+        let mut b = String::new();
+        let m = self.0.lock().unwrap();
+
+        header(
+            &mut b,
+            "source_hashes",
+            "gauge",
+            "Info-hashes listed by the full-scrape source",
+        );
+        for (source, n) in &m.sources {
+            let _ = writeln!(b, "{P}source_hashes{{source=\"{}\"}} {n}", escape(source));
+        }
+        header(&mut b, "resolved_total", "counter", "Resolved torrents");
+        let _ = writeln!(b, "{P}resolved_total {}", m.resolved);
+        header(
+            &mut b,
+            "failed_total",
+            "counter",
+            "Info-hashes skipped by the reason",
+        );
+        for (reason, n) in &m.failed {
+            let _ = writeln!(b, "{P}failed_total{{reason=\"{reason}\"}} {n}");
+        }
+        header(
+            &mut b,
+            "banned_total",
+            "counter",
+            "Info-hashes banned by the reason",
+        );
+        for (reason, n) in &m.banned {
+            let _ = writeln!(b, "{P}banned_total{{reason=\"{reason}\"}} {n}");
+        }
+        m.resolve_seconds.write(
+            &mut b,
+            &format!("{P}resolve_seconds"),
+            "Time from the peers lookup to the torrent commit",
+        );
+        m.preload_seconds.write(
+            &mut b,
+            &format!("{P}preload_seconds"),
+            "Time of the content preload",
+        );
+        header(
+            &mut b,
+            "preload_bytes_total",
+            "counter",
+            "Preloaded content size",
+        );
+        let _ = writeln!(b, "{P}preload_bytes_total {}", m.preload_bytes);
+        if let Some(d) = m.disk_usage {
+            header(
+                &mut b,
+                "disk_usage_bytes",
+                "gauge",
+                "Disk usage of the preload and content roots",
+            );
+            let _ = writeln!(b, "{P}disk_usage_bytes {d}");
+        }
+        header(
+            &mut b,
+            "iterations_total",
+            "counter",
+            "Completed queue iterations",
+        );
+        let _ = writeln!(b, "{P}iterations_total {}", m.iterations);
+        header(
+            &mut b,
+            "iteration_seconds",
+            "gauge",
+            "Duration of the last queue iteration",
+        );
+        let _ = writeln!(b, "{P}iteration_seconds {}", m.iteration_seconds);
+
+        let health = tracker.health();
+        header(
+            &mut b,
+            "tracker_peers",
+            "gauge",
+            "Peers returned by the last announce",
+        );
+        for (url, h) in &health {
+            let _ = writeln!(
+                b,
+                "{P}tracker_peers{{tracker=\"{}\"}} {}",
+                escape(url),
+                h.peers
+            );
+        }
+        header(
+            &mut b,
+            "tracker_announces_total",
+            "counter",
+            "Announces by the result",
+        );
+        for (url, h) in &health {
+            let u = escape(url);
+            let _ = writeln!(
+                b,
+                "{P}tracker_announces_total{{tracker=\"{u}\",result=\"success\"}} {}",
+                h.success
+            );
+            let _ = writeln!(
+                b,
+                "{P}tracker_announces_total{{tracker=\"{u}\",result=\"failure\"}} {}",
+                h.failure
+            );
+        }
+        header(&mut b, "i2p_sessions", "gauge", "Active I2P sessions");
+        let _ = writeln!(b, "{P}i2p_sessions {i2p_sessions}");
+        b
+    }
+}
+
+/// Serve the metrics on `GET /metrics` at given `address`
+pub async fn listen(
+    address: SocketAddr,
+    metrics: Arc<Metrics>,
+    tracker: Arc<tracker::Buffer>,
+    i2p: Arc<RwLock<HashMap<String, I2pSession>>>,
+) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address).await?;
+    info!("metrics listening on http://{address}/metrics");
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, client) = match listener.accept().await {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!("[metrics] accept failed: {e}");
+                    continue;
+                }
+            };
+            let metrics = metrics.clone();
+            let tracker = tracker.clone();
+            let i2p = i2p.clone();
+            tokio::spawn(async move {
+                // the request line is enough to route, skip the headers
+                let mut request = [0; 1024];
+                let n = match stream.read(&mut request).await {
+                    Ok(n) => n,
+                    Err(e) => {
+                        warn!("[metrics] read from {client} failed: {e}");
+                        return;
+                    }
+                };
+                let response = match String::from_utf8_lossy(&request[..n])
+                    .split_whitespace()
+                    .take(2)
+                    .collect::<Vec<&str>>()[..]
+                {
+                    ["GET", "/metrics"] => {
+                        let body = metrics.render(&tracker, i2p.read().await.len());
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                            body.len()
+                        )
+                    }
+                    _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        .into(),
+                };
+                if let Err(e) = stream.write_all(response.as_bytes()).await {
+                    warn!("[metrics] write to {client} failed: {e}")
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Calculate total size of the files in `paths`, recursively
+///
+/// * blocking, run it with `spawn_blocking`
+/// * hardlinked files (e.g. the content blobs) and overlapping `paths` are counted once
+pub fn disk_usage(paths: &[PathBuf]) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    let mut seen = HashSet::new();
+    let mut total = 0;
+    for p in paths {
+        if std::fs::metadata(p).is_ok_and(|m| seen.insert((m.dev(), m.ino()))) {
+            total += walk(p, &mut seen)
+        }
+    }
+    total
+}
+
+// Local members
+
+/// Metric names prefix
+const P: &str = "btracker_crawler_";
+
+fn header(b: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(b, "# HELP {P}{name} {help}");
+    let _ = writeln!(b, "# TYPE {P}{name} {kind}");
+}
+
+fn walk(path: &Path, seen: &mut HashSet<(u64, u64)>) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::read_dir(path)
+        .map(|d| {
+            d.filter_map(|e| e.ok())
+                .map(|e| match e.metadata() {
+                    Ok(m) if m.is_dir() && seen.insert((m.dev(), m.ino())) => walk(&e.path(), seen),
+                    Ok(m) if m.is_file() && (m.nlink() < 2 || seen.insert((m.dev(), m.ino()))) => {
+                        m.len()
+                    }
+                    _ => 0,
+                })
+                .sum()
+        })
+        .unwrap_or_default()
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
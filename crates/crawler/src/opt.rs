@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -7,4 +7,28 @@ pub struct Opt {
     /// Path to `config.toml`
     #[arg(long, short)]
     pub config: PathBuf,
+
+    /// Crawler mode (`run` by default)
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Crawl the sources in the endless loop
+    Run,
+    /// Crawl the sources once, then exit (e.g. for cron or systemd timers)
+    Once,
+    /// Resolve single torrent with the configured trackers and preload rules
+    Resolve {
+        /// Info-hash or magnet link
+        target: String,
+    },
+    /// Commit local `.torrent` file(s)
+    Import {
+        /// Path to the `.torrent` file or directory of files
+        path: PathBuf,
+    },
+    /// Print the current full-scrape queue: `info_hash seeders leechers downloaded sources`
+    ListSources,
 }
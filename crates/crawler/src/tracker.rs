@@ -240,25 +240,77 @@ pub struct I2pSession {
     pub socket: SocketAddr,
 }
 
-pub struct Buffer(pub Vec<Tracker>);
+/// Announce results of the tracker
+#[derive(Clone, Default)]
+pub struct Health {
+    pub success: u64,
+    pub failure: u64,
+    pub last_error: Option<String>,
+    /// Peers returned by the last successful announce
+    pub peers: usize,
+}
+
+pub struct Buffer {
+    trackers: Vec<Tracker>,
+    health: std::sync::Mutex<HashMap<String, Health>>,
+}
 
 impl Buffer {
+    pub fn new(trackers: Vec<Tracker>) -> Self {
+        Self {
+            health: std::sync::Mutex::new(HashMap::with_capacity(trackers.len())),
+            trackers,
+        }
+    }
+
     /// Return peers from trackers
     pub async fn peers(&self, info_hash: &Id20) -> Result<HashSet<SocketAddr>> {
         let mut peers = HashSet::new(); // unique peers buffer collected from all trackers
 
-        for tracker in self.0.iter() {
+        for tracker in self.trackers.iter() {
             debug!(
                 "[tracker] get peers from {} for {}...",
                 tracker.url(),
                 info_hash.as_string(),
             );
-            peers.extend(tracker.peers(info_hash).await?)
+            let result = tracker.peers(info_hash).await;
+            {
+                let mut h = self.health.lock().unwrap();
+                let h = h.entry(tracker.url().to_string()).or_default();
+                match result {
+                    Ok(ref p) => {
+                        h.success += 1;
+                        h.peers = p.len()
+                    }
+                    Err(ref e) => {
+                        h.failure += 1;
+                        h.last_error = Some(e.to_string())
+                    }
+                }
+            }
+            peers.extend(result?)
         }
 
         Ok(peers)
     }
 
+    /// Return URLs of all trackers
+    pub fn urls(&self) -> Vec<String> {
+        self.trackers.iter().map(|t| t.url().to_string()).collect()
+    }
+
+    /// Return announce results by the tracker URL
+    pub fn health(&self) -> Vec<(String, Health)> {
+        let h = self.health.lock().unwrap();
+        self.urls()
+            .into_iter()
+            .map(|u| {
+                let v = h.get(&u).cloned().unwrap_or_default();
+                (u, v)
+            })
+            .collect()
+    }
+
     /// Build magnet URI (`librqbit` impl dependency)
     pub fn magnet(&self, info_hash: &str) -> String {
         format!("magnet:?xt=urn:btih:{info_hash}")
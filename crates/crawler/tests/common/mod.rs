@@ -0,0 +1,493 @@
+//! Loopback stand-ins for the crawler integration tests:
+//! the HTTP tracker (full-scrape and announce) and the BitTorrent seeder of the fixture torrents
+
+#![allow(dead_code)] // every test binary uses own subset
+
+use sha1::{Digest, Sha1};
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    path::PathBuf,
+    sync::Arc,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+
+pub const PIECE_LENGTH: usize = 16384;
+
+/// Our `ut_metadata` extension ID
+const UT_METADATA: u8 = 3;
+
+// Bencode
+
+pub enum B {
+    I(i64),
+    S(Vec<u8>),
+    L(Vec<B>),
+    D(BTreeMap<Vec<u8>, B>),
+}
+
+impl B {
+    pub fn s(value: impl AsRef<[u8]>) -> Self {
+        Self::S(value.as_ref().to_vec())
+    }
+
+    pub fn d<const N: usize>(items: [(&str, B); N]) -> Self {
+        Self::D(
+            items
+                .into_iter()
+                .map(|(k, v)| (k.as_bytes().to_vec(), v))
+                .collect(),
+        )
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut b = Vec::new();
+        self.write(&mut b);
+        b
+    }
+
+    fn write(&self, b: &mut Vec<u8>) {
+        match self {
+            Self::I(i) => b.extend(format!("i{i}e").as_bytes()),
+            Self::S(s) => {
+                b.extend(format!("{}:", s.len()).as_bytes());
+                b.extend(s)
+            }
+            Self::L(l) => {
+                b.push(b'l');
+                for i in l {
+                    i.write(b)
+                }
+                b.push(b'e')
+            }
+            Self::D(d) => {
+                b.push(b'd');
+                for (k, v) in d {
+                    Self::S(k.clone()).write(b);
+                    v.write(b)
+                }
+                b.push(b'e')
+            }
+        }
+    }
+
+    /// Decode the first value of `b`, returns the value with its encoded length
+    pub fn decode(b: &[u8]) -> Option<(Self, usize)> {
+        match *b.first()? {
+            b'i' => {
+                let e = b.iter().position(|c| *c == b'e')?;
+                Some((
+                    Self::I(std::str::from_utf8(&b[1..e]).ok()?.parse().ok()?),
+                    e + 1,
+                ))
+            }
+            b'l' => {
+                let mut l = Vec::new();
+                let mut p = 1;
+                while *b.get(p)? != b'e' {
+                    let (v, n) = Self::decode(&b[p..])?;
+                    l.push(v);
+                    p += n
+                }
+                Some((Self::L(l), p + 1))
+            }
+            b'd' => {
+                let mut d = BTreeMap::new();
+                let mut p = 1;
+                while *b.get(p)? != b'e' {
+                    let (k, n) = Self::decode(&b[p..])?;
+                    p += n;
+                    let (v, n) = Self::decode(&b[p..])?;
+                    p += n;
+                    match k {
+                        Self::S(k) => d.insert(k, v),
+                        _ => return None,
+                    };
+                }
+                Some((Self::D(d), p + 1))
+            }
+            _ => {
+                let c = b.iter().position(|c| *c == b':')?;
+                let l: usize = std::str::from_utf8(&b[..c]).ok()?.parse().ok()?;
+                Some((Self::S(b.get(c + 1..c + 1 + l)?.to_vec()), c + 1 + l))
+            }
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        match self {
+            Self::D(d) => d.get(key.as_bytes()),
+            _ => None,
+        }
+    }
+
+    pub fn int(&self) -> Option<i64> {
+        match self {
+            Self::I(i) => Some(*i),
+            _ => None,
+        }
+    }
+}
+
+// Fixtures
+
+/// Multi-file torrent built from the in-memory content
+pub struct Fixture {
+    pub info_hash: [u8; 20],
+    /// Bencoded `info` dictionary (the metadata exchanged by `ut_metadata`)
+    pub info: Vec<u8>,
+    /// Content of all files, concatenated
+    pub data: Vec<u8>,
+}
+
+impl Fixture {
+    pub fn new(name: &str, files: &[(&str, Vec<u8>)]) -> Self {
+        let data: Vec<u8> = files.iter().flat_map(|(_, d)| d.clone()).collect();
+        let pieces: Vec<u8> = data
+            .chunks(PIECE_LENGTH)
+            .flat_map(|p| Sha1::digest(p).to_vec())
+            .collect();
+        let info = B::d([
+            (
+                "files",
+                B::L(
+                    files
+                        .iter()
+                        .map(|(path, d)| {
+                            B::d([
+                                ("length", B::I(d.len() as i64)),
+                                ("path", B::L(path.split('/').map(B::s).collect())),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+            ("name", B::s(name)),
+            ("piece length", B::I(PIECE_LENGTH as i64)),
+            ("pieces", B::S(pieces)),
+        ])
+        .encode();
+        Self {
+            info_hash: Sha1::digest(&info).into(),
+            info,
+            data,
+        }
+    }
+
+    pub fn hex(&self) -> String {
+        self.info_hash.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    fn pieces(&self) -> usize {
+        self.data.len().div_ceil(PIECE_LENGTH)
+    }
+}
+
+/// Create empty directory for the test storage
+pub fn root(name: &str) -> PathBuf {
+    let mut p = std::env::temp_dir();
+    p.push(format!(
+        "btracker-crawler-test-{name}-{}",
+        std::process::id()
+    ));
+    if p.exists() {
+        std::fs::remove_dir_all(&p).unwrap()
+    }
+    std::fs::create_dir_all(&p).unwrap();
+    p
+}
+
+// HTTP tracker
+
+/// Serve `/scrape` (full-scrape of the `listed` info-hashes) and `/announce` (the `peers` by info-hash)
+///
+/// Returns the base URL by the host alias, e.g. `http://localhost:1234`,
+/// so the loopback seeder passes the self-exclusion filter of the crawler (by the tracker host)
+pub async fn tracker(listed: Vec<[u8; 20]>, peers: HashMap<[u8; 20], Vec<SocketAddrV4>>) -> String {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let url = format!("http://localhost:{}", listener.local_addr().unwrap().port());
+    let listed = Arc::new(listed);
+    let peers = Arc::new(peers);
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let listed = listed.clone();
+            let peers = peers.clone();
+            tokio::spawn(async move {
+                let mut request = Vec::new();
+                let mut b = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match stream.read(&mut b).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => request.extend(&b[..n]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request);
+                let target = request.split_whitespace().nth(1).unwrap_or_default();
+                let body = if target.starts_with("/scrape") {
+                    B::d([(
+                        "files",
+                        B::D(
+                            listed
+                                .iter()
+                                .map(|h| {
+                                    (
+                                        h.to_vec(),
+                                        B::d([
+                                            ("complete", B::I(1)),
+                                            ("downloaded", B::I(0)),
+                                            ("incomplete", B::I(0)),
+                                        ]),
+                                    )
+                                })
+                                .collect(),
+                        ),
+                    )])
+                } else if target.starts_with("/announce") {
+                    let compact = query(target, "info_hash")
+                        .and_then(|h| <[u8; 20]>::try_from(h).ok())
+                        .and_then(|h| peers.get(&h))
+                        .map(|p| {
+                            p.iter()
+                                .flat_map(|a| {
+                                    let mut b = a.ip().octets().to_vec();
+                                    b.extend(a.port().to_be_bytes());
+                                    b
+                                })
+                                .collect::<Vec<u8>>()
+                        })
+                        .unwrap_or_default();
+                    B::d([
+                        ("complete", B::I(1)),
+                        ("incomplete", B::I(0)),
+                        ("interval", B::I(1800)),
+                        ("peers", B::S(compact)),
+                    ])
+                } else {
+                    B::d([("failure reason", B::s("not found"))])
+                }
+                .encode();
+                let mut response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                )
+                .into_bytes();
+                response.extend(body);
+                let _ = stream.write_all(&response).await;
+            });
+        }
+    });
+    url
+}
+
+// Webhook
+
+/// Serve the webhook, responding with the `statuses` in order (the last one repeats)
+///
+/// Returns the endpoint URL and the receiver of the request bodies
+pub async fn webhook(statuses: Vec<u16>) -> (String, mpsc::UnboundedReceiver<Vec<u8>>) {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut statuses = statuses.into_iter().peekable();
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut request = Vec::new();
+            let mut b = [0; 1024];
+            let body = loop {
+                if let Some(i) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                    let length = String::from_utf8_lossy(&request[..i])
+                        .lines()
+                        .find_map(|l| {
+                            let (k, v) = l.split_once(':')?;
+                            k.eq_ignore_ascii_case("content-length")
+                                .then(|| v.trim().parse::<usize>().ok())?
+                        })
+                        .unwrap_or_default();
+                    if request.len() >= i + 4 + length {
+                        break request[i + 4..i + 4 + length].to_vec();
+                    }
+                }
+                match stream.read(&mut b).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => request.extend(&b[..n]),
+                }
+            };
+            let status = if statuses.len() > 1 {
+                statuses.next()
+            } else {
+                statuses.peek().copied()
+            }
+            .unwrap_or(200);
+            sender.send(body).unwrap();
+            let _ = stream
+                .write_all(
+                    format!(
+                        "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    )
+                    .as_bytes(),
+                )
+                .await;
+        }
+    });
+    (url, receiver)
+}
+
+/// Get the percent-decoded `key` value from the request `target`
+fn query(target: &str, key: &str) -> Option<Vec<u8>> {
+    let v = target
+        .split_once('?')?
+        .1
+        .split('&')
+        .find_map(|p| p.strip_prefix(key)?.strip_prefix('='))?
+        .as_bytes();
+    let mut b = Vec::with_capacity(v.len());
+    let mut i = 0;
+    while i < v.len() {
+        if v[i] == b'%' {
+            b.push(u8::from_str_radix(std::str::from_utf8(v.get(i + 1..i + 3)?).ok()?, 16).ok()?);
+            i += 3
+        } else {
+            b.push(v[i]);
+            i += 1
+        }
+    }
+    Some(b)
+}
+
+// BitTorrent seeder
+
+/// Seed the `fixture` on the loopback address
+///
+/// * set `is_metadata` to `false` to stall the metadata exchange (never answer requests)
+///
+/// Returns the seeder address to announce
+pub async fn seeder(fixture: Arc<Fixture>, is_metadata: bool) -> SocketAddrV4 {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let address = match listener.local_addr().unwrap() {
+        SocketAddr::V4(a) => a,
+        SocketAddr::V6(_) => unreachable!(),
+    };
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let fixture = fixture.clone();
+            tokio::spawn(async move {
+                let _ = peer(stream, &fixture, is_metadata).await;
+            });
+        }
+    });
+    address
+}
+
+async fn peer(mut stream: TcpStream, fixture: &Fixture, is_metadata: bool) -> std::io::Result<()> {
+    // handshake
+    let mut h = [0; 68];
+    stream.read_exact(&mut h).await?;
+    if h[0] != 19 || &h[1..20] != b"BitTorrent protocol" || h[28..48] != fixture.info_hash {
+        return Ok(());
+    }
+    let mut b = vec![19];
+    b.extend(b"BitTorrent protocol");
+    b.extend([0, 0, 0, 0, 0, 0x10, 0, 0]); // BEP 10 extension protocol
+    b.extend(fixture.info_hash);
+    b.extend(b"-BT0001-loopbackpeer");
+    stream.write_all(&b).await?;
+
+    // have all pieces
+    let mut bitfield = vec![0u8; fixture.pieces().div_ceil(8)];
+    for i in 0..fixture.pieces() {
+        bitfield[i / 8] |= 0x80 >> (i % 8)
+    }
+    message(&mut stream, 5, &bitfield).await?;
+
+    // extended handshake
+    let mut e = vec![0];
+    e.extend(
+        B::d([
+            ("m", B::d([("ut_metadata", B::I(UT_METADATA as i64))])),
+            ("metadata_size", B::I(fixture.info.len() as i64)),
+        ])
+        .encode(),
+    );
+    message(&mut stream, 20, &e).await?;
+    message(&mut stream, 1, &[]).await?; // unchoke
+
+    // the `ut_metadata` ID assigned by the remote peer
+    let mut remote_ut_metadata = None;
+    loop {
+        let mut l = [0; 4];
+        stream.read_exact(&mut l).await?;
+        let l = u32::from_be_bytes(l) as usize;
+        if l == 0 {
+            continue; // keep-alive
+        }
+        let mut m = vec![0; l];
+        stream.read_exact(&mut m).await?;
+        match m[0] {
+            // interested
+            2 => message(&mut stream, 1, &[]).await?,
+            // request
+            6 if m.len() == 13 => {
+                let u = |i: usize| u32::from_be_bytes([m[i], m[i + 1], m[i + 2], m[i + 3]]);
+                let (index, begin, length) = (u(1) as usize, u(5) as usize, u(9) as usize);
+                let offset = index * PIECE_LENGTH + begin;
+                let Some(data) = fixture.data.get(offset..offset + length) else {
+                    return Ok(());
+                };
+                let mut p = Vec::with_capacity(8 + length);
+                p.extend(&m[1..9]);
+                p.extend(data);
+                message(&mut stream, 7, &p).await?
+            }
+            // extended
+            20 if m.len() > 1 => {
+                let Some((d, _)) = B::decode(&m[2..]) else {
+                    continue;
+                };
+                match m[1] {
+                    0 => {
+                        remote_ut_metadata = d
+                            .get("m")
+                            .and_then(|m| m.get("ut_metadata"))
+                            .and_then(|i| i.int())
+                    }
+                    UT_METADATA if is_metadata => {
+                        let (Some(id), Some(0), Some(piece)) = (
+                            remote_ut_metadata,
+                            d.get("msg_type").and_then(|t| t.int()),
+                            d.get("piece").and_then(|p| p.int()),
+                        ) else {
+                            continue;
+                        };
+                        let begin = piece as usize * PIECE_LENGTH;
+                        let end = (begin + PIECE_LENGTH).min(fixture.info.len());
+                        let mut r = vec![id as u8];
+                        r.extend(
+                            B::d([
+                                ("msg_type", B::I(1)),
+                                ("piece", B::I(piece)),
+                                ("total_size", B::I(fixture.info.len() as i64)),
+                            ])
+                            .encode(),
+                        );
+                        r.extend(&fixture.info[begin..end]);
+                        message(&mut stream, 20, &r).await?
+                    }
+                    _ => (), // stall
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+async fn message(stream: &mut TcpStream, id: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut b = Vec::with_capacity(5 + payload.len());
+    b.extend((payload.len() as u32 + 1).to_be_bytes());
+    b.push(id);
+    b.extend(payload);
+    stream.write_all(&b).await
+}
@@ -0,0 +1,182 @@
+//! End-to-end crawl on loopback:
+//! full-scrape → announce → `add_torrent` → preload → `commit`
+
+mod common;
+
+use btpeer::http::query::Scrape;
+use btracker_crawler::{
+    Crawler, Options, config::timeout::Timeout, full_scrape::FullScrape, hook::Kind,
+    tracker::Tracker,
+};
+use btracker_fs::{
+    crawler::{Storage, validate::info_hash},
+    index::Index,
+    layout::Layout,
+};
+use common::{Fixture, root, seeder, tracker};
+use regex::Regex;
+use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
+use url::Url;
+
+/// Build the crawler for the stand-in `tracker` URL
+fn crawler(root: &Path, tracker: &str, regex: Option<&str>) -> Crawler {
+    let url = |path: &str| Url::parse(&format!("{tracker}{path}")).unwrap();
+    Crawler::builder(
+        Storage::init(
+            root.to_path_buf(),
+            None,
+            regex.map(|r| Regex::new(r).unwrap()),
+            None,
+            None,
+            None,
+            Layout::Flat,
+        )
+        .unwrap(),
+    )
+    .source(FullScrape {
+        name: "loopback".into(),
+        proxy: None,
+        query: Scrape::new(url("/scrape").as_str(), None).unwrap(),
+        timeout: Duration::from_secs(5),
+        url: url("/scrape"),
+    })
+    .tracker(Tracker::Default {
+        peers_limit: None,
+        port: 6881,
+        proxy: None,
+        timeout: Duration::from_secs(5),
+        url: url("/announce"),
+    })
+    .options(Options {
+        timeout: Timeout {
+            add_torrent_seconds: 5,
+            torrent_preload_seconds: 10,
+            ..Timeout::default()
+        },
+        ..Options::default()
+    })
+    .build()
+    .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn resolve_metadata() {
+    let fixture = Arc::new(Fixture::new(
+        "album",
+        &[("01.flac", vec![1; 40000]), ("cover.jpg", vec![2; 20000])],
+    ));
+    let peer = seeder(fixture.clone(), true).await;
+    let tracker = tracker(
+        vec![fixture.info_hash],
+        HashMap::from([(fixture.info_hash, vec![peer])]),
+    )
+    .await;
+    let root = root("resolve-metadata");
+    let mut crawler = crawler(&root, &tracker, None);
+    let mut events = crawler.events();
+
+    crawler.run_once().await.unwrap();
+
+    let h = fixture.hex();
+    let torrent = std::fs::read(root.join(format!("{h}.torrent"))).unwrap();
+    assert_eq!(info_hash(&torrent).unwrap(), h);
+    assert!(root.join(format!("{h}.json")).is_file());
+    assert!(!root.join(&h).exists()); // no preload regex, no content
+
+    let indexed = Index::open(&root).read().unwrap();
+    assert_eq!(indexed.len(), 1);
+    assert_eq!(indexed[0].info_hash, h);
+    assert_eq!(indexed[0].name.as_deref(), Some("album"));
+
+    let e = events.try_recv().unwrap();
+    assert_eq!(e.event, Kind::Resolved);
+    assert_eq!(e.info_hash, h);
+    assert_eq!(e.name.as_deref(), Some("album"));
+
+    // committed torrent is not resolved again
+    crawler.run_once().await.unwrap();
+    assert!(events.try_recv().is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn preload_filter() {
+    let cover = (0..20000).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+    let fixture = Arc::new(Fixture::new(
+        "album",
+        &[("01.flac", vec![1; 40000]), ("cover.jpg", cover.clone())],
+    ));
+    let peer = seeder(fixture.clone(), true).await;
+    let tracker = tracker(
+        vec![fixture.info_hash],
+        HashMap::from([(fixture.info_hash, vec![peer])]),
+    )
+    .await;
+    let root = root("preload-filter");
+    let mut crawler = crawler(&root, &tracker, Some(r"\.jpg$"));
+    let mut events = crawler.events();
+
+    crawler.run_once().await.unwrap();
+
+    let h = fixture.hex();
+    assert!(root.join(format!("{h}.torrent")).is_file());
+    // matching file is preloaded and verified, others are skipped
+    assert_eq!(
+        std::fs::read(root.join(&h).join("cover.jpg")).unwrap(),
+        cover
+    );
+    assert!(!root.join(&h).join("01.flac").exists());
+    // temporary data is cleaned up
+    assert!(!root.join(format!(".{h}")).exists());
+
+    let meta = std::fs::read_to_string(root.join(format!("{h}.json"))).unwrap();
+    assert!(meta.contains("cover.jpg") && meta.contains("01.flac"));
+
+    let e = events.try_recv().unwrap();
+    assert_eq!(e.event, Kind::Resolved);
+    assert!(e.paths.iter().any(|p| p.ends_with("cover.jpg")));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ban_unresolvable() {
+    let fixture = Arc::new(Fixture::new("stalled", &[("data.bin", vec![3; 1000])]));
+    // the seeder accepts connections, but never sends the metadata
+    let peer = seeder(fixture.clone(), false).await;
+    let tracker = tracker(
+        vec![fixture.info_hash],
+        HashMap::from([(fixture.info_hash, vec![peer])]),
+    )
+    .await;
+    let root = root("ban-unresolvable");
+    let mut crawler = crawler(&root, &tracker, None);
+    let mut events = crawler.events();
+
+    let mut banned = Vec::new();
+    for _ in 0..3 {
+        crawler.run_once().await.unwrap();
+        let mut n = 0;
+        while let Ok(e) = events.try_recv() {
+            assert_eq!(e.event, Kind::Banned);
+            n += 1
+        }
+        banned.push(n)
+    }
+    // banned on timeout, skipped on the next iteration, then retried
+    assert_eq!(banned, [1, 0, 1]);
+    assert!(!root.join(format!("{}.torrent", fixture.hex())).exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn skip_unlisted_peers() {
+    let fixture = Fixture::new("lonely", &[("data.bin", vec![4; 1000])]);
+    // listed by the full-scrape, but no peers announced
+    let tracker = tracker(vec![fixture.info_hash], HashMap::new()).await;
+    let root = root("skip-unlisted-peers");
+    let mut crawler = crawler(&root, &tracker, None);
+    let mut events = crawler.events();
+
+    crawler.run_once().await.unwrap();
+
+    // not banned: peers may appear on the next iteration
+    assert!(events.try_recv().is_err());
+    assert!(!root.join(format!("{}.torrent", fixture.hex())).exists());
+}
@@ -0,0 +1,85 @@
+//! Webhook delivery on loopback: JSON payload and retries of the failed requests
+
+mod common;
+
+use btracker_crawler::hook::{Event, Hooks, Kind};
+use common::webhook;
+
+/// Build the webhook config with no delay between the retries
+fn config(url: &str, retries: u32) -> btracker_crawler::config::hook::Hook {
+    toml::from_str(&format!(
+        "webhook = \"{url}\"\nretries = {retries}\nretry_delay_seconds = 0"
+    ))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn deliver_webhook() {
+    let (url, mut requests) = webhook(vec![500, 503, 200]).await;
+    let hooks = Hooks::init(config(&url, 3)).unwrap();
+
+    let mut event = Event::new(Kind::Resolved, "0123456789abcdef0123456789abcdef01234567");
+    event.name = Some("album".into());
+    hooks.emit(event);
+    hooks.shutdown().await;
+
+    // failed twice, delivered on the third attempt
+    let mut bodies = Vec::new();
+    while let Ok(b) = requests.try_recv() {
+        bodies.push(b)
+    }
+    assert_eq!(bodies.len(), 3);
+    for b in bodies {
+        let json: serde_json::Value = serde_json::from_slice(&b).unwrap();
+        assert_eq!(json["event"], "resolved");
+        assert_eq!(
+            json["info_hash"],
+            "0123456789abcdef0123456789abcdef01234567"
+        );
+        assert_eq!(json["name"], "album");
+        assert!(json["paths"].as_array().unwrap().is_empty());
+        assert!(json["time"].is_string());
+    }
+}
+
+#[tokio::test]
+async fn give_up_webhook() {
+    let (url, mut requests) = webhook(vec![500]).await;
+    let hooks = Hooks::init(config(&url, 2)).unwrap();
+
+    hooks.emit(Event::new(
+        Kind::Deleted,
+        "0123456789abcdef0123456789abcdef01234567",
+    ));
+    hooks.shutdown().await;
+
+    // first attempt and the retries only
+    let mut n = 0;
+    while requests.try_recv().is_ok() {
+        n += 1
+    }
+    assert_eq!(n, 3);
+}
+
+#[tokio::test]
+async fn filter_events() {
+    let (url, mut requests) = webhook(vec![200]).await;
+    let mut config = config(&url, 0);
+    config.events = vec![Kind::Resolved];
+    let hooks = Hooks::init(config).unwrap();
+
+    hooks.emit(Event::new(
+        Kind::Banned,
+        "0123456789abcdef0123456789abcdef01234567",
+    ));
+    hooks.emit(Event::new(
+        Kind::Resolved,
+        "0123456789abcdef0123456789abcdef01234567",
+    ));
+    hooks.shutdown().await;
+
+    let b = requests.try_recv().unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&b).unwrap();
+    assert_eq!(json["event"], "resolved");
+    assert!(requests.try_recv().is_err());
+}
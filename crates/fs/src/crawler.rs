@@ -1,8 +1,55 @@
+mod blob;
+pub mod failure;
+pub mod filter;
+pub mod lease;
+pub mod lifecycle;
+mod piece;
+pub mod spam;
+pub mod validate;
+
+use crate::{
+    hybrid::{Aliases, InfoHashes},
+    index::{Index, Record},
+    layout::{Layout, is_info_hash},
+    meta::{self, Meta},
+    verify::{self, Status, Verification},
+};
+use blob::Blobs;
+use chrono::{DateTime, Utc};
+use lease::{Lease, Leases};
+use librqbit_core::torrent_metainfo::{TorrentMetaV1Owned, torrent_from_bytes};
+use lifecycle::{Event, Expire, Policy};
 use regex::Regex;
-use std::{collections::HashSet, fs, io::Error, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Locations of the torrent data persisted by `Storage::commit`
+pub struct Committed {
+    /// Torrent name, if defined
+    pub name: Option<String>,
+    /// Absolute path to the `.torrent` file
+    pub torrent: PathBuf,
+    /// Absolute paths to the preloaded files
+    pub files: Vec<PathBuf>,
+}
 
 pub struct Storage {
+    blobs: Option<Blobs>,
+    /// Root of the preloaded content, the same as `root` by default
+    content: PathBuf,
+    index: Index,
+    layout: Layout,
     root: PathBuf,
+    /// Sources recorded in the sidecars of the committed torrents,
+    /// to not read them on every full-scrape iteration
+    sources: Mutex<HashMap<String, Vec<String>>>,
+    /// Coordinate with other workers sharing the `root`
+    pub leases: Option<Leases>,
     pub max_filecount: Option<usize>,
     pub max_filesize: Option<u64>,
     pub regex: Option<Regex>,
@@ -11,21 +58,50 @@ pub struct Storage {
 impl Storage {
     // Constructors
 
+    /// * `content` to keep the preloaded files apart from the metadata (e.g. on the cheap disk)
     pub fn init(
         root: PathBuf,
+        content: Option<PathBuf>,
         regex: Option<Regex>,
         max_filecount: Option<usize>,
         max_filesize: Option<u64>,
+        blobs: Option<PathBuf>,
+        layout: Layout,
     ) -> Result<Self, String> {
         // make sure given path is valid and exist
         if !root.is_dir() {
             return Err("Storage root is not directory".into());
         }
+        let root = root.canonicalize().map_err(|e| e.to_string())?;
+        let content = match content {
+            Some(c) => {
+                if !c.is_dir() {
+                    return Err("Storage content root is not directory".into());
+                }
+                c.canonicalize().map_err(|e| e.to_string())?
+            }
+            None => root.clone(),
+        };
         Ok(Self {
+            blobs: blobs.map(|b| Blobs::init(b, &content)).transpose()?,
+            content,
+            index: {
+                let i = Index::init(&root).map_err(|e| e.to_string())?;
+                // build the index of the storage created by the previous versions
+                if let Err(e) = i.read() {
+                    log::info!("rebuild metadata index ({e})...");
+                    let n = Index::rebuild(&root).map_err(|e| e.to_string())?;
+                    log::info!("metadata index rebuilt with {n} torrents")
+                }
+                i
+            },
+            layout,
+            leases: None,
             max_filecount,
             max_filesize,
             regex,
-            root: root.canonicalize().map_err(|e| e.to_string())?,
+            root,
+            sources: Mutex::new(HashMap::new()),
         })
     }
 
@@ -33,34 +109,80 @@ impl Storage {
 
     /// Persist torrent bytes and preloaded content,
     /// cleanup tmp data on success (see rqbit#408)
+    ///
+    /// * preloaded files are verified against the torrent piece hashes,
+    ///   corrupted files are not published
+    /// * `meta` sidecar is completed with the resolve time and preload errors
+    /// * returns the locations of the persisted data
     pub fn commit(
         &self,
         info_hash: &str,
         torrent_bytes: Vec<u8>,
         persist_files: Option<HashSet<PathBuf>>,
-    ) -> Result<(), Error> {
+        mut meta: Meta,
+    ) -> Result<Committed, Error> {
         // persist preloaded files
         let permanent_dir = self.permanent_dir(info_hash, true)?;
         // init temporary path without creating the dir (delegate to `librqbit`)
         let tmp_dir = self.tmp_dir(info_hash, false)?;
+        // make sure the shard directory exists
+        fs::create_dir_all(self.layout.dir(&self.root, info_hash))?;
+        let verification_file = self.verification(info_hash);
+        let torrent: TorrentMetaV1Owned =
+            torrent_from_bytes(&torrent_bytes).map_err(Error::other)?;
+        let mut committed = Committed {
+            name: torrent.info.name.as_ref().map(|n| n.to_string()),
+            torrent: self.torrent(info_hash),
+            files: Vec::new(),
+        };
         if let Some(files) = persist_files {
-            let components_count = permanent_dir.components().count(); // count root offset once
+            // map relative filenames to their location in the torrent data
+            let mut offsets = HashMap::with_capacity(files.len());
+            let mut total_length = 0;
+            for details in torrent.info.iter_file_details().map_err(Error::other)? {
+                if let Ok(p) = details.filename.to_pathbuf() {
+                    offsets.insert(p, (total_length, details.len));
+                }
+                total_length += details.len
+            }
+            let mut verification = Verification::default();
             for file in files {
                 // build the absolute path for the relative torrent filename
                 let tmp_file = {
                     let mut p = PathBuf::from(&tmp_dir);
-                    p.push(file);
+                    p.push(&file);
                     p.canonicalize()?
                 };
                 // make sure preload path is referring to the expected location
-                assert!(tmp_file.starts_with(&self.root) && !tmp_file.is_dir());
-                // build new permanent path /root/info-hash
-                let mut permanent_file = PathBuf::from(&permanent_dir);
-                for component in tmp_file.components().skip(components_count) {
-                    permanent_file.push(component)
+                assert!(tmp_file.starts_with(&self.content) && !tmp_file.is_dir());
+                // check the data integrity before publish
+                let status = match offsets.get(&file) {
+                    Some((offset, length)) => piece::verify(
+                        &tmp_file,
+                        *offset,
+                        *length,
+                        total_length,
+                        torrent.info.piece_length,
+                        torrent.info.pieces.as_ref(),
+                    )?,
+                    None => Status::Failed,
+                };
+                if status == Status::Failed {
+                    log::warn!(
+                        "verification failed for tmp file `{}`, skip.",
+                        tmp_file.to_string_lossy()
+                    );
+                    meta.errors.push(format!(
+                        "verification failed for `{}`",
+                        file.to_string_lossy()
+                    ));
+                    verification.0.insert(file, status);
+                    continue;
                 }
-                // make sure segments count is same to continue
-                assert!(tmp_file.components().count() == permanent_file.components().count());
+                verification.0.insert(file, status);
+                // build new permanent path /content/[shard/]info-hash
+                let mut permanent_file = PathBuf::from(&permanent_dir);
+                permanent_file.push(tmp_file.strip_prefix(&tmp_dir).map_err(Error::other)?);
                 // move `persist_files` from temporary to permanent location
                 fs::create_dir_all(permanent_file.parent().unwrap())?;
                 fs::rename(&tmp_file, &permanent_file)?;
@@ -69,29 +191,173 @@ impl Storage {
                     tmp_file.to_string_lossy(),
                     permanent_file.to_string_lossy()
                 );
+                // deduplicate identical content between torrents
+                if let Some(ref b) = self.blobs {
+                    b.link(&permanent_file)?
+                }
+                committed.files.push(permanent_file)
             }
+            verification.write(&verification_file)?
+        } else if verification_file.exists() {
+            fs::remove_file(&verification_file)?
         }
         // cleanup temporary data
         if tmp_dir.exists() {
             fs::remove_dir_all(&tmp_dir)?;
             log::debug!("clean tmp data `{}`", tmp_dir.to_string_lossy())
         }
-        // persist torrent bytes to file (on previous operations success)
-        let torrent_file = self.torrent(info_hash);
-        fs::write(&torrent_file, torrent_bytes)?;
+        // persist metadata sidecar
+        let resolved_at = Utc::now();
+        meta.resolved_at = Some(resolved_at);
+        meta.write(&self.meta(info_hash))?;
+        self.sources
+            .lock()
+            .unwrap()
+            .insert(info_hash.to_string(), meta.sources.clone());
+        // map the v2 info-hash of the hybrid torrent
+        if let Some(v2) = InfoHashes::from_bytes(&torrent_bytes)
+            .map_err(Error::other)?
+            .v2
+        {
+            Aliases::open(&self.root).insert(&v2, &torrent.info_hash)?
+        }
+        let record = Record::from_bytes(&torrent_bytes, resolved_at)
+            .map_err(Error::other)?
+            .with_meta(&meta);
+        // persist torrent bytes to file (on previous operations success),
+        // atomically, as the readers (and other workers) rely on its existence
+        let part = committed
+            .torrent
+            .with_file_name(format!(".{info_hash}.{}.{P}", std::process::id()));
+        fs::write(&part, torrent_bytes)?;
+        fs::rename(&part, &committed.torrent)?;
         log::debug!(
             "persist torrent bytes for `{}`",
-            torrent_file.to_string_lossy()
+            committed.torrent.to_string_lossy()
         );
+        // list the torrent by the frontends
+        self.index.insert(&record)?;
+        // cleanup previous version stored by another layout (e.g. in the middle of migration)
+        let current = self.layout.dir(&self.root, info_hash);
+        for layout in [Layout::Flat, Layout::Sharded] {
+            let d = layout.dir(&self.root, info_hash);
+            if d == current {
+                continue;
+            }
+            for suffix in [".torrent", ".json", ".verify"] {
+                let f = d.join(format!("{info_hash}{suffix}"));
+                if f.is_file() {
+                    fs::remove_file(&f)?;
+                    log::debug!("clean previous data `{}`", f.to_string_lossy())
+                }
+            }
+            let p = layout.dir(&self.content, info_hash).join(info_hash);
+            if p.is_dir() {
+                self.clear(&p)?
+            }
+        }
+        Ok(committed)
+    }
+
+    /// Update the lifecycle state of all committed torrents, apply the retention `policy`
+    ///
+    /// * `listed` returns the seeders count of the info-hash listed by the full-scrape sources
+    /// * expired torrents are resolved again if they appear in the sources later
+    /// * torrents removed in the middle (e.g. by another worker) are skipped,
+    ///   make sure it is not running by another worker at the same time
+    /// * returns the changed info-hashes with the related events
+    pub fn lifecycle(
+        &self,
+        listed: impl Fn(&str) -> Option<u32>,
+        policy: &Policy,
+    ) -> Result<Vec<(String, Event)>, Error> {
+        let now = Utc::now();
+        let mut events = Vec::new();
+        for dir in Layout::dirs(&self.root)? {
+            let mut torrents = Vec::new();
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_file()
+                    && path.extension().is_some_and(|e| e == "torrent")
+                    && let Some(s) = path.file_stem()
+                    && is_info_hash(&s.to_string_lossy())
+                {
+                    torrents.push(s.to_string_lossy().to_string())
+                }
+            }
+            for info_hash in torrents {
+                match self.update_lifecycle(&dir, &info_hash, listed(&info_hash), policy, now) {
+                    Ok(e) => events.extend(e.into_iter().map(|e| (info_hash.clone(), e))),
+                    // archived or deleted by another worker (or manually) in the middle
+                    Err(e) if e.kind() == ErrorKind::NotFound => {
+                        log::debug!("torrent {info_hash} not found, skip lifecycle update.")
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    /// Lock the `info_hash` before handle, when the `root` is shared with other workers
+    /// * returns `None` if it is handled by another worker
+    pub fn lease(&self, info_hash: &str) -> Result<Option<Lease>, Error> {
+        match self.leases {
+            Some(ref l) => l.acquire(info_hash),
+            None => Ok(Some(Lease::exclusive())),
+        }
+    }
+
+    /// Remove temporary data of the rejected `info_hash`
+    pub fn discard(&self, info_hash: &str) -> Result<(), Error> {
+        let p = self.tmp_dir(info_hash, false)?;
+        if p.exists() {
+            fs::remove_dir_all(&p)?;
+            log::debug!("clean tmp data `{}`", p.to_string_lossy())
+        }
         Ok(())
     }
 
+    /// Append the full-scrape `sources` not recorded yet to the sidecar of the committed `info_hash`
+    /// * the sidecar is read once, then only when the `sources` are not known yet
+    /// * returns `true` if the sidecar was updated
+    pub fn add_sources(&self, info_hash: &str, sources: &[String]) -> Result<bool, Error> {
+        let mut known = self.sources.lock().unwrap();
+        if known
+            .get(info_hash)
+            .is_some_and(|k| sources.iter().all(|s| k.contains(s)))
+        {
+            return Ok(false);
+        }
+        let Some((k, p)) = self.key(info_hash).and_then(|k| {
+            let p = Layout::find(&self.root, &k, &format!(".{}", meta::E))?;
+            Some((k, p))
+        }) else {
+            return Ok(false);
+        };
+        let mut meta = Meta::read(&p)?;
+        let l = meta.sources.len();
+        for s in sources {
+            if !meta.sources.contains(s) {
+                meta.sources.push(s.clone())
+            }
+        }
+        let is_updated = meta.sources.len() != l;
+        if is_updated {
+            meta.write(&p)?;
+            self.reindex(p.parent().unwrap(), &k, &meta)?
+        }
+        known.insert(info_hash.to_string(), meta.sources);
+        Ok(is_updated)
+    }
+
     // Actions
 
     /// Build the absolute path to the temporary directory
     /// * optionally creates directory if not exists
+    /// * located in the `content` root, to move the preloaded files without copy
     pub fn tmp_dir(&self, info_hash: &str, is_create: bool) -> Result<PathBuf, Error> {
-        let mut p = PathBuf::from(&self.root);
+        let mut p = PathBuf::from(&self.content);
         p.push(tmp_component(info_hash));
         assert!(!p.is_file());
         if is_create && !p.exists() {
@@ -104,17 +370,141 @@ impl Storage {
     /// Build the absolute path to the permanent directory
     /// * optionally removes directory with its content
     fn permanent_dir(&self, info_hash: &str, is_clear: bool) -> Result<PathBuf, Error> {
-        let mut p = PathBuf::from(&self.root);
+        let mut p = self.layout.dir(&self.content, info_hash);
         p.push(info_hash);
         assert!(!p.is_file());
         if is_clear && p.exists() {
-            // clean previous data
-            fs::remove_dir_all(&p)?;
-            log::debug!("clean previous data `{}`", p.to_string_lossy())
+            self.clear(&p)?
         }
         Ok(p)
     }
 
+    /// Remove preloaded content directory, release its shared blobs
+    fn clear(&self, dir: &PathBuf) -> Result<(), Error> {
+        if let Some(ref b) = self.blobs {
+            b.release_dir(dir)?
+        }
+        fs::remove_dir_all(dir)?;
+        log::debug!("clean previous data `{}`", dir.to_string_lossy());
+        Ok(())
+    }
+
+    /// Update the lifecycle state of the committed `info_hash` in the `dir`
+    /// * `listed` is the seeders count, if listed by the full-scrape sources
+    /// * returns the related events
+    fn update_lifecycle(
+        &self,
+        dir: &Path,
+        info_hash: &str,
+        listed: Option<u32>,
+        policy: &Policy,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Event>, Error> {
+        let mut events = Vec::new();
+        let meta_file = dir.join(format!("{info_hash}.{}", meta::E));
+        let mut meta = Meta::read(&meta_file).unwrap_or_default();
+        let mut is_changed = false;
+        match listed {
+            Some(seeders) => {
+                is_changed |= meta.delisted_at.take().is_some();
+                if seeders > 0 {
+                    is_changed |= meta.dead_at.take().is_some()
+                } else if meta.dead_at.is_none() {
+                    meta.dead_at = Some(now);
+                    is_changed = true;
+                    events.push(Event::Dead)
+                }
+            }
+            None => {
+                if meta.delisted_at.is_none() {
+                    meta.delisted_at = Some(now);
+                    is_changed = true;
+                    events.push(Event::Delisted)
+                }
+            }
+        }
+        let age = meta
+            .inactive_since()
+            .map(|t| (now - t).num_seconds().max(0) as u64);
+        if let Some(age) = age
+            && let Some((seconds, ref expire)) = policy.expire
+            && age >= seconds
+        {
+            let torrent_file = dir.join(format!("{info_hash}.torrent"));
+            // read before any change, to skip the torrent expired by another worker
+            let torrent_bytes = fs::read(&torrent_file)?;
+            self.drop_content(dir, info_hash)?;
+            // expired hybrid torrent is not available by the v2 info-hash anymore
+            if let Ok(InfoHashes { v2: Some(v2), .. }) = InfoHashes::from_bytes(&torrent_bytes) {
+                Aliases::open(&self.root).remove(&v2)?
+            }
+            self.index.remove(info_hash)?;
+            self.sources.lock().unwrap().remove(info_hash);
+            match expire {
+                Expire::Archive(archive) => {
+                    meta.write(&meta_file)?;
+                    fs::create_dir_all(archive)?;
+                    // move the torrent file last, as it is the commit point
+                    fs::rename(&meta_file, archive.join(format!("{info_hash}.{}", meta::E)))?;
+                    fs::rename(&torrent_file, archive.join(format!("{info_hash}.torrent")))?;
+                    events.push(Event::Archived)
+                }
+                Expire::Delete => {
+                    fs::remove_file(&torrent_file)?;
+                    if meta_file.exists() {
+                        fs::remove_file(&meta_file)?
+                    }
+                    events.push(Event::Deleted)
+                }
+            }
+            return Ok(events);
+        }
+        if let Some(age) = age
+            && let Some(seconds) = policy.drop_content
+            && age >= seconds
+            && meta.dropped_at.is_none()
+        {
+            self.drop_content(dir, info_hash)?;
+            meta.dropped_at = Some(now);
+            is_changed = true;
+            events.push(Event::Dropped)
+        }
+        if is_changed {
+            meta.write(&meta_file)?;
+            self.reindex(dir, info_hash, &meta)?
+        }
+        Ok(events)
+    }
+
+    /// Update the index record of the committed `info_hash` in the `dir` by its changed `meta`
+    fn reindex(&self, dir: &Path, info_hash: &str, meta: &Meta) -> Result<(), Error> {
+        let torrent_file = dir.join(format!("{info_hash}.torrent"));
+        let time = match meta.resolved_at {
+            Some(t) => t,
+            None => torrent_file.metadata()?.modified()?.into(),
+        };
+        self.index.insert(
+            &Record::from_bytes(&fs::read(&torrent_file)?, time)
+                .map_err(Error::other)?
+                .with_meta(meta),
+        )
+    }
+
+    /// Remove preloaded content and its verification status from the `dir` of the `info_hash`
+    fn drop_content(&self, dir: &Path, info_hash: &str) -> Result<(), Error> {
+        let p = Layout::of(&self.root, dir)
+            .dir(&self.content, info_hash)
+            .join(info_hash);
+        if p.is_dir() {
+            self.clear(&p)?
+        }
+        let v = dir.join(format!("{info_hash}.{}", verify::E));
+        if v.is_file() {
+            fs::remove_file(v)?
+        }
+        Ok(())
+    }
+
     // Getters
 
     /// Get root location for `Self`
@@ -122,20 +512,60 @@ impl Storage {
         &self.root
     }
 
+    /// Get root location of the preloaded content
+    pub fn content(&self) -> &PathBuf {
+        &self.content
+    }
+
+    /// Get the metadata index of the committed torrents
+    pub fn index(&self) -> &Index {
+        &self.index
+    }
+
     /// Check the given hash is contain resolved torrent file
+    /// * lookup all layouts
+    /// * the truncated v2 info-hash of the hybrid torrent is supported
     pub fn contains_torrent(&self, info_hash: &str) -> Result<bool, Error> {
-        fs::exists(self.torrent(info_hash))
+        Ok(self.key(info_hash).is_some())
+    }
+
+    /// Get the storage key of the resolved torrent by its v1 or v2 `info_hash`
+    fn key(&self, info_hash: &str) -> Option<String> {
+        if Layout::find(&self.root, info_hash, ".torrent").is_some() {
+            return Some(info_hash.to_string());
+        }
+        let k = Aliases::open(&self.root).get(info_hash)?.as_string();
+        Layout::find(&self.root, &k, ".torrent").map(|_| k)
     }
 
     /// Get absolute path to the torrent file
     fn torrent(&self, info_hash: &str) -> PathBuf {
-        let mut p = PathBuf::from(&self.root);
+        let mut p = self.layout.dir(&self.root, info_hash);
         p.push(format!("{info_hash}.torrent"));
         assert!(!p.is_dir());
         p
     }
+
+    /// Get absolute path to the metadata sidecar
+    fn meta(&self, info_hash: &str) -> PathBuf {
+        let mut p = self.layout.dir(&self.root, info_hash);
+        p.push(format!("{info_hash}.{}", meta::E));
+        assert!(!p.is_dir());
+        p
+    }
+
+    /// Get absolute path to the preloaded files verification status
+    fn verification(&self, info_hash: &str) -> PathBuf {
+        let mut p = self.layout.dir(&self.root, info_hash);
+        p.push(format!("{info_hash}.{}", verify::E));
+        assert!(!p.is_dir());
+        p
+    }
 }
 
+/// Partially written file extension
+const P: &str = "part";
+
 /// Build constant path component
 fn tmp_component(info_hash: &str) -> String {
     format!(".{info_hash}")
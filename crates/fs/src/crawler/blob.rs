@@ -0,0 +1,236 @@
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Error, ErrorKind, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+/// Content-addressed store for the preloaded files
+///
+/// * every unique file is kept once as `{root}/{ab}/{abcd..}`,
+///   torrent paths are materialized as hardlinks (or copies, when the link is not possible)
+/// * references are counted in the `{ab}/{abcd..}.refs` file, by the `{info_hash}/path`
+///   relative to the storage `base` (in any `Layout`)
+/// * the blob and its references are changed under the `{ab}/{abcd..}.lock` file,
+///   so the store can be shared between workers
+/// * unused blobs and references to the removed files are cleaned by `btracker-fs fsck --blobs`
+pub struct Blobs {
+    base: PathBuf,
+    root: PathBuf,
+}
+
+impl Blobs {
+    // Constructors
+
+    pub fn init(root: PathBuf, base: &Path) -> Result<Self, String> {
+        if !root.is_dir() {
+            return Err("Blobs root is not directory".into());
+        }
+        Ok(Self {
+            base: base.to_path_buf(),
+            root: root.canonicalize().map_err(|e| e.to_string())?,
+        })
+    }
+
+    // Actions
+
+    /// Replace the given `file` with the shared blob reference
+    pub fn link(&self, file: &Path) -> Result<(), Error> {
+        let blob = self.blob(&hash(file)?)?;
+        let _lock = Lock::acquire(&blob)?;
+        if blob.exists() {
+            fs::remove_file(file)?;
+            if fs::hard_link(&blob, file).is_err() {
+                fs::copy(&blob, file)?;
+            }
+            log::debug!(
+                "link `{}` to existing blob `{}`",
+                file.to_string_lossy(),
+                blob.to_string_lossy()
+            )
+        } else {
+            if fs::hard_link(file, &blob).is_err() {
+                fs::copy(file, &blob)?;
+            }
+            log::debug!(
+                "create blob `{}` from `{}`",
+                blob.to_string_lossy(),
+                file.to_string_lossy()
+            )
+        }
+        let mut refs = refs(&blob)?;
+        let r = self.relative(file);
+        if !refs.contains(&r) {
+            refs.push(r)
+        }
+        write_refs(&blob, &refs)
+    }
+
+    /// Drop the given `file` reference, remove the blob if it is not in use anymore
+    /// * the `file` itself is not removed
+    pub fn release(&self, file: &Path) -> Result<(), Error> {
+        let blob = self.blob(&hash(file)?)?;
+        let _lock = Lock::acquire(&blob)?;
+        if !blob.exists() {
+            return Ok(()); // not linked
+        }
+        let r = self.relative(file);
+        let mut refs = refs(&blob)?;
+        refs.retain(|p| *p != r);
+        if refs.is_empty() {
+            remove(&blob)?
+        } else {
+            write_refs(&blob, &refs)?
+        }
+        Ok(())
+    }
+
+    /// Release all files in the given `dir` (recursively)
+    pub fn release_dir(&self, dir: &Path) -> Result<(), Error> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.release_dir(&path)?
+            } else {
+                self.release(&path)?
+            }
+        }
+        Ok(())
+    }
+
+    // Helpers
+
+    /// Build the blob path, create its shard directory if not exists
+    fn blob(&self, hash: &str) -> Result<PathBuf, Error> {
+        let mut p = PathBuf::from(&self.root);
+        p.push(&hash[..2]);
+        if !p.exists() {
+            fs::create_dir(&p)?
+        }
+        p.push(hash);
+        Ok(p)
+    }
+
+    /// Build layout-independent reference `{info_hash}/path` for the given `file`
+    fn relative(&self, file: &Path) -> String {
+        file.strip_prefix(&self.base)
+            .unwrap_or(file)
+            .components()
+            .skip_while(|c| c.as_os_str().len() == 2) // shards
+            .collect::<PathBuf>()
+            .to_string_lossy()
+            .into()
+    }
+}
+
+// Local members
+
+/// References file extension
+const R: &str = "refs";
+
+/// Lock file extension
+const L: &str = "lock";
+
+/// Lock age to consider it left by the interrupted worker
+const T: Duration = Duration::from_secs(60);
+
+/// Exclusive lock of the blob references, released on drop
+struct Lock(PathBuf);
+
+impl Lock {
+    /// Wait for the lock of the `blob`, take over the stale one
+    fn acquire(blob: &Path) -> Result<Self, Error> {
+        let path = blob.with_extension(L);
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(Self(path)),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if fs::metadata(&path)
+                        .and_then(|m| m.modified())
+                        .is_ok_and(|t| t.elapsed().is_ok_and(|t| t > T))
+                    {
+                        log::warn!("take over stale lock `{}`", path.to_string_lossy());
+                        fs::remove_file(&path).or_else(|e| {
+                            if e.kind() == ErrorKind::NotFound {
+                                Ok(())
+                            } else {
+                                Err(e)
+                            }
+                        })?;
+                        continue;
+                    }
+                    thread::sleep(Duration::from_millis(50))
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.0) {
+            log::warn!("could not release lock `{}`: {e}", self.0.to_string_lossy())
+        }
+    }
+}
+
+/// Feed the hasher by `io::copy`
+struct Hasher(Sha256);
+
+impl Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn hash(file: &Path) -> Result<String, Error> {
+    let mut h = Hasher(Sha256::new());
+    io::copy(&mut fs::File::open(file)?, &mut h)?;
+    Ok(h.0.finalize().iter().map(|b| format!("{b:02x}")).collect())
+}
+
+fn refs(blob: &Path) -> Result<Vec<String>, Error> {
+    let p = blob.with_extension(R);
+    if !p.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(fs::read_to_string(p)?
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(|l| l.into())
+        .collect())
+}
+
+fn write_refs(blob: &Path, refs: &[String]) -> Result<(), Error> {
+    let p = blob.with_extension(R);
+    let t = blob.with_file_name(format!(
+        ".{}.{}.tmp",
+        blob.file_name().unwrap().to_string_lossy(),
+        std::process::id()
+    ));
+    let mut f = fs::File::create(&t)?;
+    for r in refs {
+        writeln!(f, "{r}")?
+    }
+    f.sync_all()?;
+    fs::rename(t, p)
+}
+
+fn remove(blob: &Path) -> Result<(), Error> {
+    let r = blob.with_extension(R);
+    if r.exists() {
+        fs::remove_file(r)?
+    }
+    if blob.exists() {
+        fs::remove_file(blob)?
+    }
+    log::debug!("remove unused blob `{}`", blob.to_string_lossy());
+    Ok(())
+}
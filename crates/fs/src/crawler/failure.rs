@@ -0,0 +1,96 @@
+//! Persistent list of the info-hashes rejected by the crawler,
+//! to not resolve them again on the next queue iterations
+//!
+//! * `{root}/.failures` lines: `info_hash\ttime\treason`
+//! * `{root}/.quarantine/{info_hash}.torrent` rejected metainfo (optionally)
+
+use chrono::Utc;
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{Error, Write},
+    path::{Path, PathBuf},
+};
+
+pub struct Failures {
+    index: HashMap<String, String>,
+    path: PathBuf,
+    quarantine: Option<PathBuf>,
+}
+
+impl Failures {
+    // Constructors
+
+    /// Load existing failures of the storage `root`
+    /// * optionally keep the rejected metainfo in the quarantine directory
+    pub fn init(root: &Path, is_quarantine: bool) -> Result<Self, Error> {
+        let path = root.join(F);
+        let mut index = HashMap::new();
+        if path.exists() {
+            for line in fs::read_to_string(&path)?.lines() {
+                let mut c = line.splitn(3, '\t');
+                if let Some(info_hash) = c.next()
+                    && !info_hash.is_empty()
+                {
+                    index.insert(
+                        info_hash.to_string(),
+                        c.nth(1).unwrap_or_default().to_string(),
+                    );
+                }
+            }
+        }
+        Ok(Self {
+            index,
+            path,
+            quarantine: if is_quarantine {
+                let q = root.join(Q);
+                if !q.exists() {
+                    fs::create_dir(&q)?
+                }
+                Some(q)
+            } else {
+                None
+            },
+        })
+    }
+
+    // Actions
+
+    /// Append the `info_hash` to the list with given `reason`,
+    /// move the `torrent_bytes` to the quarantine (if enabled)
+    pub fn insert(
+        &mut self,
+        info_hash: &str,
+        reason: &str,
+        torrent_bytes: Option<&[u8]>,
+    ) -> Result<(), Error> {
+        let reason = reason.replace(['\t', '\n', '\r'], " ");
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(
+                format!("{info_hash}\t{}\t{reason}\n", Utc::now().to_rfc3339()).as_bytes(),
+            )?;
+        if let Some(ref q) = self.quarantine
+            && let Some(b) = torrent_bytes
+        {
+            fs::write(q.join(format!("{info_hash}.torrent")), b)?
+        }
+        self.index.insert(info_hash.to_string(), reason);
+        Ok(())
+    }
+
+    // Getters
+
+    /// Get the rejection reason, if the `info_hash` is listed
+    pub fn get(&self, info_hash: &str) -> Option<&str> {
+        self.index.get(info_hash).map(|r| r.as_str())
+    }
+}
+
+/// Failures list filename in the storage root
+const F: &str = ".failures";
+
+/// Quarantine directory name in the storage root
+const Q: &str = ".quarantine";
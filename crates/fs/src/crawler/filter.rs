@@ -0,0 +1,72 @@
+//! Crawler-side policy to skip unwanted torrents before the content preload
+
+use librqbit_core::torrent_metainfo::{TorrentMetaV1Owned, torrent_from_bytes};
+use regex::Regex;
+use std::collections::HashSet;
+
+#[derive(Default)]
+pub struct Filter {
+    /// Skip torrents with the `private` flag
+    pub skip_private: bool,
+    /// Skip torrents with name match the pattern
+    pub name_deny: Option<Regex>,
+    /// Skip torrents with any file path match the pattern
+    pub file_deny: Option<Regex>,
+    /// Skip torrents with total size less than given bytes
+    pub min_size: Option<u64>,
+    /// Skip torrents with total size more than given bytes
+    pub max_size: Option<u64>,
+    /// Skip torrents contain any file with the extension (lowercase, without dot)
+    pub extensions: HashSet<String>,
+}
+
+impl Filter {
+    /// Check the `torrent_bytes` against the policy
+    ///
+    /// * returns the skip reason on match
+    pub fn check(&self, torrent_bytes: &[u8]) -> Result<(), String> {
+        let torrent: TorrentMetaV1Owned = torrent_from_bytes(torrent_bytes)
+            .map_err(|e| format!("could not parse metadata: {e}"))?;
+        if self.skip_private && torrent.info.private {
+            return Err("private torrent".into());
+        }
+        if let Some(ref r) = self.name_deny
+            && let Some(ref name) = torrent.info.name
+            && r.is_match(&String::from_utf8_lossy(name.as_ref()))
+        {
+            return Err("name match the deny list".into());
+        }
+        let mut total_length = 0;
+        for details in torrent
+            .info
+            .iter_file_details()
+            .map_err(|e| format!("could not read files: {e}"))?
+        {
+            total_length += details.len;
+            if self.file_deny.is_none() && self.extensions.is_empty() {
+                continue;
+            }
+            let path = details
+                .filename
+                .to_string()
+                .map_err(|e| format!("invalid file path: {e}"))?;
+            if let Some(ref r) = self.file_deny
+                && r.is_match(&path)
+            {
+                return Err(format!("file `{path}` match the deny list"));
+            }
+            if let Some((_, e)) = path.rsplit_once('.')
+                && self.extensions.contains(&e.to_lowercase())
+            {
+                return Err(format!("file `{path}` extension is denied"));
+            }
+        }
+        if self.min_size.is_some_and(|s| total_length < s) {
+            return Err(format!("total size {total_length} is less than allowed"));
+        }
+        if self.max_size.is_some_and(|s| total_length > s) {
+            return Err(format!("total size {total_length} is more than allowed"));
+        }
+        Ok(())
+    }
+}
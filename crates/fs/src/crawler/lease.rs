@@ -0,0 +1,236 @@
+//! Expiring info-hash locks, to share the storage root between multiple crawler workers
+//! (e.g. on different machines with the same NFS mount)
+//!
+//! * `{root}/.lease/{info_hash}` file contains `owner\texpires` line (UNIX time)
+//! * new lock is written aside and hard-linked in place, that is atomic on NFS also;
+//!   the expired one is moved aside before the takeover, so only one worker can win the race
+//! * keep the worker clocks synchronized, the expiration time is shared between them
+
+use chrono::Utc;
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub struct Leases {
+    dir: PathBuf,
+    owner: String,
+    ttl: Duration,
+}
+
+impl Leases {
+    // Constructors
+
+    /// Init the lease directory of the storage `root` for the worker of given unique `owner` name
+    /// * the `ttl` must cover the longest info-hash handle time, or the lease has to be renewed
+    pub fn init(root: &Path, owner: String, ttl: Duration) -> Result<Self, Error> {
+        if owner.is_empty() || owner.contains(['\t', '\n', '\r', '/']) {
+            return Err(Error::other(format!("invalid lease owner `{owner}`")));
+        }
+        let dir = root.join(D);
+        if !dir.exists() {
+            fs::create_dir(&dir)?
+        }
+        Ok(Self { dir, owner, ttl })
+    }
+
+    // Actions
+
+    /// Try to lock the `info_hash` for this worker
+    /// * returns `None` if the lock is held by another worker
+    /// * the lock is released on the `Lease` drop
+    pub fn acquire(&self, info_hash: &str) -> Result<Option<Lease>, Error> {
+        let path = self.dir.join(info_hash);
+        let lease = Lease {
+            path: Some(path.clone()),
+            owner: self.owner.clone(),
+            ttl: self.ttl,
+        };
+        if lease.create()? {
+            return Ok(Some(lease));
+        }
+        let stale = match Record::read(&path)? {
+            Some(r) if r.owner != self.owner && !r.is_expired() => return Ok(None),
+            Some(r) => r,
+            None => return Ok(None), // released in the middle
+        };
+        // move the expired lock aside first:
+        // the rename succeeds for one of the competing workers only
+        let aside = lease.aside().unwrap();
+        if let Err(e) = fs::rename(&path, &aside) {
+            return if e.kind() == ErrorKind::NotFound {
+                Ok(None)
+            } else {
+                Err(e)
+            };
+        }
+        // the lock is renewed by another worker since the read, restore it
+        if Record::read(&aside)?.is_some_and(|r| r != stale) {
+            fs::rename(&aside, &path)?;
+            return Ok(None);
+        }
+        fs::remove_file(&aside)?;
+        log::debug!(
+            "take over expired lease of `{info_hash}` from `{}`",
+            stale.owner
+        );
+        Ok(if lease.create()? { Some(lease) } else { None })
+    }
+
+    /// Remove expired locks, left by the interrupted workers
+    /// * returns the number of removed locks
+    pub fn gc(&self) -> Result<usize, Error> {
+        let mut removed = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path
+                .file_name()
+                .is_some_and(|n| !n.to_string_lossy().starts_with('.'))
+                && Record::read(&path)?.is_some_and(|r| r.is_expired())
+                && self
+                    .acquire(&path.file_name().unwrap().to_string_lossy())?
+                    .is_some()
+            {
+                removed += 1 // released on drop
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Lock of the info-hash, released on drop
+pub struct Lease {
+    /// Lock file, or `None` if the storage is not shared
+    path: Option<PathBuf>,
+    owner: String,
+    ttl: Duration,
+}
+
+impl Lease {
+    // Constructors
+
+    /// Build the lease of the storage without coordination
+    pub fn exclusive() -> Self {
+        Self {
+            path: None,
+            owner: String::new(),
+            ttl: Duration::ZERO,
+        }
+    }
+
+    // Actions
+
+    /// Extend the lock expiration time by the `ttl` from now
+    /// * returns `false` if the lock is lost (expired and taken over by another worker)
+    pub fn renew(&self) -> Result<bool, Error> {
+        let Some(ref path) = self.path else {
+            return Ok(true);
+        };
+        if !self.is_held()? {
+            return Ok(false);
+        }
+        let tmp = self.aside().unwrap();
+        fs::write(&tmp, self.record().to_string())?;
+        fs::rename(&tmp, path)?;
+        Ok(true)
+    }
+
+    /// Check the lock is still owned by this worker
+    pub fn is_held(&self) -> Result<bool, Error> {
+        match self.path {
+            Some(ref p) => Ok(Record::read(p)?.is_some_and(|r| r.owner == self.owner)),
+            None => Ok(true),
+        }
+    }
+
+    // Tools
+
+    /// Create the lock file exclusively, with its content complete
+    /// * returns `false` if it exists already
+    fn create(&self) -> Result<bool, Error> {
+        let Some(ref path) = self.path else {
+            return Ok(true);
+        };
+        let tmp = self.aside().unwrap();
+        fs::write(&tmp, self.record().to_string())?;
+        let linked = fs::hard_link(&tmp, path);
+        fs::remove_file(&tmp)?;
+        match linked {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Build the unique path of this worker next to the lock file
+    /// * hidden from the `Leases::gc`
+    fn aside(&self) -> Option<PathBuf> {
+        self.path.as_ref().map(|p| {
+            p.with_file_name(format!(
+                ".{}.{}.{}",
+                p.file_name().unwrap().to_string_lossy(),
+                self.owner,
+                std::process::id()
+            ))
+        })
+    }
+
+    fn record(&self) -> Record {
+        Record {
+            owner: self.owner.clone(),
+            expires: Utc::now().timestamp() + self.ttl.as_secs() as i64,
+        }
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        if let Some(ref path) = self.path
+            && self.is_held().unwrap_or_default()
+            && let Err(e) = fs::remove_file(path)
+        {
+            log::warn!("could not release lease `{}`: {e}", path.to_string_lossy())
+        }
+    }
+}
+
+// Local members
+
+/// Lease directory name in the storage root
+const D: &str = ".lease";
+
+#[derive(PartialEq)]
+struct Record {
+    owner: String,
+    /// UNIX time
+    expires: i64,
+}
+
+impl Record {
+    /// Read the lock file, if exists
+    /// * malformed lock is expired
+    fn read(path: &Path) -> Result<Option<Self>, Error> {
+        let data = match fs::read_to_string(path) {
+            Ok(d) => d,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut c = data.trim_end().splitn(2, '\t');
+        Ok(Some(Self {
+            owner: c.next().unwrap_or_default().to_string(),
+            expires: c.next().and_then(|e| e.parse().ok()).unwrap_or_default(),
+        }))
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires <= Utc::now().timestamp()
+    }
+}
+
+impl std::fmt::Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}\t{}", self.owner, self.expires)
+    }
+}
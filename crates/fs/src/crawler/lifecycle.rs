@@ -0,0 +1,49 @@
+//! Retention policy of the committed torrents, see `Storage::lifecycle`
+
+use std::{fmt::Display, path::PathBuf};
+
+#[derive(Default)]
+pub struct Policy {
+    /// Drop the preloaded content of torrents inactive for given seconds
+    pub drop_content: Option<u64>,
+    /// Archive or delete the torrents inactive for given seconds
+    pub expire: Option<(u64, Expire)>,
+}
+
+pub enum Expire {
+    /// Move the `.torrent` and its sidecar to given directory
+    /// * should be located on the same filesystem as the storage root
+    Archive(PathBuf),
+    /// Remove all the torrent data
+    Delete,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// Not listed by the full-scrape sources anymore
+    Delisted,
+    /// No seeders reported by the full-scrape sources
+    Dead,
+    /// Preloaded content removed
+    Dropped,
+    /// Torrent moved to the archive
+    Archived,
+    /// Torrent removed
+    Deleted,
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Delisted => "delisted",
+                Self::Dead => "dead",
+                Self::Dropped => "dropped",
+                Self::Archived => "archived",
+                Self::Deleted => "deleted",
+            }
+        )
+    }
+}
@@ -0,0 +1,59 @@
+use crate::verify::Status;
+use sha1::{Digest, Sha1};
+use std::{
+    fs::File,
+    io::{Error, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// Check pieces fully covered by the `file` located at `offset` of the torrent data
+///
+/// * `pieces` is the concatenated SHA-1 hashes from the torrent info
+pub fn verify(
+    file: &Path,
+    offset: u64,
+    length: u64,
+    total_length: u64,
+    piece_length: u32,
+    pieces: &[u8],
+) -> Result<Status, Error> {
+    let l = piece_length as u64;
+    if l == 0 || length == 0 {
+        return Ok(Status::Partial);
+    }
+    let end = offset + length;
+    // first piece starting inside the file
+    let first = offset.div_ceil(l);
+    // piece after the last one ending inside the file (the last torrent piece may be shorter)
+    let last = if end == total_length {
+        end.div_ceil(l)
+    } else {
+        end / l
+    };
+    let mut f = File::open(file)?;
+    if f.metadata()?.len() != length {
+        return Ok(Status::Failed);
+    }
+    let mut buffer = vec![0; piece_length as usize];
+    for i in first..last {
+        let start = i * l;
+        let size = (start + l).min(total_length) - start;
+        f.seek(SeekFrom::Start(start - offset))?;
+        f.read_exact(&mut buffer[..size as usize])?;
+        let expected = match pieces.get(i as usize * 20..i as usize * 20 + 20) {
+            Some(h) => h,
+            None => return Ok(Status::Failed),
+        };
+        if Sha1::digest(&buffer[..size as usize]).as_slice() != expected {
+            return Ok(Status::Failed);
+        }
+    }
+    // make sure there is no data out of the checked pieces
+    Ok(
+        if first * l == offset && (last * l).min(total_length) == end {
+            Status::Verified
+        } else {
+            Status::Partial
+        },
+    )
+}
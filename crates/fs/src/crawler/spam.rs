@@ -0,0 +1,96 @@
+//! Heuristics to detect fake releases by the torrent file list and names
+
+use crate::meta::Spam;
+use librqbit_core::torrent_metainfo::{TorrentMetaV1Owned, torrent_from_bytes};
+
+/// Score the `torrent_bytes` from 0 (clean) to 100 (spam)
+///
+/// * returns the score with names of the triggered rules
+pub fn score(torrent_bytes: &[u8]) -> Spam {
+    let torrent: TorrentMetaV1Owned = match torrent_from_bytes(torrent_bytes) {
+        Ok(t) => t,
+        Err(_) => return Spam::default(),
+    };
+    let name = torrent
+        .info
+        .name
+        .as_ref()
+        .map(|n| String::from_utf8_lossy(n.as_ref()).to_lowercase())
+        .unwrap_or_default();
+    let mut files = Vec::new();
+    let mut total_length = 0;
+    if let Ok(details) = torrent.info.iter_file_details() {
+        for d in details {
+            total_length += d.len;
+            if let Ok(p) = d.filename.to_string() {
+                files.push(p.to_lowercase())
+            }
+        }
+    }
+
+    let mut spam = Spam::default();
+    let mut rule = |name: &str, weight: u32| {
+        spam.score = (spam.score + weight).min(100);
+        spam.rules.push(name.into())
+    };
+
+    let executables = files.iter().filter(|f| is_executable(f)).count();
+    if files.iter().any(|f| is_shortcut(f)) {
+        rule("shortcut", 40)
+    }
+    if files.len() == 1 && executables == 1 {
+        rule("single_executable", 40)
+    }
+    if executables > 0 && MEDIA.iter().any(|m| name.contains(m)) {
+        rule("media_executable", 40)
+    }
+    if files.iter().any(|f| is_double_extension(f)) {
+        rule("double_extension", 50)
+    }
+    if files.iter().any(|f| is_archive(f)) && files.iter().any(|f| f.contains("password")) {
+        rule("password_archive", 50)
+    }
+    if total_length < 1024 * 1024 && name.len() > 100 {
+        rule("tiny_payload", 30)
+    }
+    spam
+}
+
+/// Name hints of the media releases
+const MEDIA: &[&str] = &[
+    "1080p", "2160p", "720p", "bdrip", "bluray", "dvdrip", "flac", "hdrip", "hdtv", "mp3",
+    "web-dl", "webrip", "x264", "x265",
+];
+
+fn extension(path: &str) -> Option<&str> {
+    path.rsplit('/').next()?.rsplit_once('.').map(|(_, e)| e)
+}
+
+fn is_executable(path: &str) -> bool {
+    extension(path).is_some_and(|e| matches!(e, "exe" | "msi" | "com" | "apk" | "jar"))
+        || is_shortcut(path)
+}
+
+fn is_shortcut(path: &str) -> bool {
+    extension(path).is_some_and(|e| matches!(e, "lnk" | "scr" | "pif" | "bat" | "cmd" | "vbs"))
+}
+
+fn is_archive(path: &str) -> bool {
+    extension(path).is_some_and(|e| matches!(e, "rar" | "zip" | "7z"))
+}
+
+/// Media file disguised executable, e.g. `movie.mp4.exe`
+fn is_double_extension(path: &str) -> bool {
+    is_executable(path)
+        && path
+            .rsplit('/')
+            .next()
+            .and_then(|n| n.rsplit_once('.'))
+            .and_then(|(s, _)| extension(s))
+            .is_some_and(|e| {
+                matches!(
+                    e,
+                    "avi" | "mkv" | "mp4" | "mp3" | "flac" | "pdf" | "jpg" | "png" | "txt"
+                )
+            })
+}
@@ -0,0 +1,65 @@
+//! Sanity checks of the torrent metainfo received from the peers, before commit
+
+use librqbit_core::torrent_metainfo::{TorrentMetaV1Owned, torrent_from_bytes};
+
+pub struct Limits {
+    /// Max files count in the torrent
+    pub max_files: usize,
+    /// Max size of the metainfo bytes
+    pub max_size: usize,
+}
+
+/// Validate the `torrent_bytes` received for the requested `info_hash`
+///
+/// * returns the rejection reason on failure
+pub fn validate(info_hash: &str, torrent_bytes: &[u8], limits: &Limits) -> Result<(), String> {
+    if torrent_bytes.len() > limits.max_size {
+        return Err(format!(
+            "metadata size {} exceeds {} bytes",
+            torrent_bytes.len(),
+            limits.max_size
+        ));
+    }
+    let torrent: TorrentMetaV1Owned =
+        torrent_from_bytes(torrent_bytes).map_err(|e| format!("could not parse metadata: {e}"))?;
+    let h = torrent.info_hash.as_string();
+    if !h.eq_ignore_ascii_case(info_hash) {
+        return Err(format!("computed info-hash `{h}` does not match"));
+    }
+    if let Some(ref name) = torrent.info.name
+        && std::str::from_utf8(name.as_ref()).is_err()
+    {
+        return Err("invalid UTF-8 name".into());
+    }
+    let mut count = 0;
+    let mut total_length = 0;
+    for details in torrent
+        .info
+        .iter_file_details()
+        .map_err(|e| format!("could not read files: {e}"))?
+    {
+        count += 1;
+        if count > limits.max_files {
+            return Err(format!("files count exceeds {}", limits.max_files));
+        }
+        // `..`, path separators and invalid UTF-8 are rejected by the iterator
+        for component in details.filename.iter_components() {
+            let c = component.map_err(|e| format!("invalid file path: {e}"))?;
+            if c.is_empty() || c == "." || c.contains('\0') {
+                return Err(format!("invalid file path component `{c}`"));
+            }
+        }
+        total_length += details.len
+    }
+    if total_length == 0 {
+        return Err("zero total length".into());
+    }
+    Ok(())
+}
+
+/// Compute the info-hash of the `torrent_bytes` (e.g. to import local files)
+pub fn info_hash(torrent_bytes: &[u8]) -> Result<String, String> {
+    let torrent: TorrentMetaV1Owned =
+        torrent_from_bytes(torrent_bytes).map_err(|e| format!("could not parse metadata: {e}"))?;
+    Ok(torrent.info_hash.as_string())
+}
@@ -0,0 +1,376 @@
+//! Consistency checker for the βtracker storage root
+//!
+//! * entries unrelated to the storage format (e.g. `theme` directory of the `btracker-http`) are ignored
+//! * the preloaded content may be kept in the separate root, with the same layout
+//! * references of the shared blobs (`preload.blobs` of the crawler) are checked, if the root is given
+//! * make sure the crawler is not running on `repair`, as its temporary data is not locked
+
+use crate::{
+    hybrid::Aliases,
+    layout::{Layout, is_info_hash},
+    meta,
+};
+use librqbit_core::{
+    Id20,
+    torrent_metainfo::{TorrentMetaV1Owned, torrent_from_bytes},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Display, Formatter, Result as FmtResult},
+    fs,
+    io::{Error, Write},
+    path::{Path, PathBuf},
+};
+
+/// Storage issue found by `check`
+#[derive(Debug)]
+pub enum Issue {
+    /// Temporary `.{info_hash}` directory left after the interrupted crawl
+    OrphanTmp(PathBuf),
+    /// Content `{info_hash}` directory without related `.torrent` file
+    OrphanContent(PathBuf),
+    /// The `.torrent` file could not be parsed
+    InvalidTorrent {
+        path: PathBuf,
+        /// Location of the preloaded content (may not exist)
+        content: PathBuf,
+        /// Metadata sidecars and v2 aliases of the torrent
+        sidecars: Vec<PathBuf>,
+        error: String,
+    },
+    /// The `.torrent` filename stem is not valid `Id20`
+    InvalidFilename(PathBuf),
+    /// The `.torrent` info-hash does not match its filename
+    HashMismatch {
+        path: PathBuf,
+        /// Location of the preloaded content (may not exist)
+        content: PathBuf,
+        /// Metadata sidecars and v2 aliases of the torrent
+        sidecars: Vec<PathBuf>,
+        info_hash: Id20,
+    },
+    /// The `{info_hash}/path` entry of the blob `.refs` file points to the missing
+    /// or invalid content (including the content removed on repair of other issues)
+    OrphanRef {
+        /// Path to the `.refs` file
+        path: PathBuf,
+        reference: String,
+    },
+    /// Shared blob without references (e.g. left by the interrupted commit)
+    OrphanBlob(PathBuf),
+}
+
+/// How to resolve the `Issue`
+pub enum Repair<'a> {
+    /// Remove invalid entries
+    Delete,
+    /// Move invalid entries to the given directory
+    /// * temporary data is removed anyway
+    Quarantine(&'a Path),
+}
+
+impl Issue {
+    // Actions
+
+    /// Resolve `Self` with given `Repair` strategy
+    pub fn repair(&self, repair: &Repair) -> Result<(), Error> {
+        match self {
+            Self::OrphanTmp(path) => fs::remove_dir_all(path),
+            Self::OrphanBlob(path) => fs::remove_file(path),
+            Self::OrphanContent(path) | Self::InvalidFilename(path) => discard(path, repair),
+            Self::InvalidTorrent {
+                path,
+                content,
+                sidecars,
+                ..
+            }
+            | Self::HashMismatch {
+                path,
+                content,
+                sidecars,
+                ..
+            } => {
+                // content directory of the broken torrent can not be trusted too
+                if content.is_dir() {
+                    discard(content, repair)?
+                }
+                for s in sidecars {
+                    if s.exists() {
+                        discard(s, repair)?
+                    }
+                }
+                discard(path, repair)
+            }
+            Self::OrphanRef { path, reference } => release(path, reference),
+        }
+    }
+
+    // Getters
+
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::OrphanTmp(path)
+            | Self::OrphanBlob(path)
+            | Self::OrphanContent(path)
+            | Self::InvalidFilename(path)
+            | Self::InvalidTorrent { path, .. }
+            | Self::HashMismatch { path, .. }
+            | Self::OrphanRef { path, .. } => path,
+        }
+    }
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::OrphanTmp(p) => write!(f, "orphan tmp directory `{}`", p.to_string_lossy()),
+            Self::OrphanBlob(p) => write!(f, "blob `{}` without references", p.to_string_lossy()),
+            Self::OrphanContent(p) => write!(
+                f,
+                "content directory `{}` without torrent file",
+                p.to_string_lossy()
+            ),
+            Self::InvalidTorrent { path, error, .. } => write!(
+                f,
+                "invalid torrent file `{}`: {error}",
+                path.to_string_lossy()
+            ),
+            Self::InvalidFilename(p) => {
+                write!(f, "invalid torrent filename `{}`", p.to_string_lossy())
+            }
+            Self::HashMismatch {
+                path, info_hash, ..
+            } => write!(
+                f,
+                "torrent file `{}` has unexpected info-hash `{}`",
+                path.to_string_lossy(),
+                info_hash.as_string()
+            ),
+            Self::OrphanRef { path, reference } => write!(
+                f,
+                "blob reference `{reference}` of `{}` points to missing content",
+                path.to_string_lossy()
+            ),
+        }
+    }
+}
+
+/// Scan the storage `root` for issues
+/// * supports any `Layout`
+/// * `content` root of the preloaded files, if kept apart from the `root`
+/// * `blobs` root of the shared preloaded files, if enabled
+pub fn check(
+    root: &Path,
+    content: Option<&Path>,
+    blobs: Option<&Path>,
+) -> Result<Vec<Issue>, Error> {
+    let content = content.unwrap_or(root);
+    let mut issues = Vec::new();
+    let mut aliases: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    for (path, v1) in Aliases::open(root).list()? {
+        aliases.entry(v1).or_default().push(path)
+    }
+    for dir in Layout::dirs(root)? {
+        check_torrents(root, content, &dir, &aliases, &mut issues)?
+    }
+    for dir in Layout::dirs(content)? {
+        check_content(root, content, &dir, &mut issues)?
+    }
+    if let Some(b) = blobs {
+        check_refs(content, b, &mut issues)?
+    }
+    Ok(issues)
+}
+
+// Local members
+
+/// Torrent file extension
+const E: &str = "torrent";
+
+/// Blob references file extension
+const R: &str = "refs";
+
+/// Verification sidecar extension (see `verify::E` of the crawler)
+const V: &str = "verify";
+
+fn check_torrents(
+    root: &Path,
+    content: &Path,
+    dir: &Path,
+    aliases: &HashMap<String, Vec<PathBuf>>,
+    issues: &mut Vec<Issue>,
+) -> Result<(), Error> {
+    for dir_entry in fs::read_dir(dir)? {
+        let path = dir_entry?.path();
+        if !path.is_file() || path.extension().is_none_or(|e| e != E) {
+            continue;
+        }
+        let stem = match path.file_stem() {
+            Some(s) if is_info_hash(&s.to_string_lossy()) => s.to_string_lossy().to_lowercase(),
+            _ => {
+                issues.push(Issue::InvalidFilename(path));
+                continue;
+            }
+        };
+        let c = Layout::of(root, dir).dir(content, &stem).join(&stem);
+        let sidecars = || {
+            let mut s = [meta::E, V]
+                .into_iter()
+                .map(|e| path.with_extension(e))
+                .filter(|p| p.is_file())
+                .collect::<Vec<_>>();
+            s.extend(aliases.get(&stem).into_iter().flatten().cloned());
+            s
+        };
+        match torrent_from_bytes(&fs::read(&path)?).map(|m: TorrentMetaV1Owned| m.info_hash) {
+            Ok(info_hash) => {
+                if info_hash.as_string() != stem {
+                    issues.push(Issue::HashMismatch {
+                        sidecars: sidecars(),
+                        path,
+                        content: c,
+                        info_hash,
+                    })
+                }
+            }
+            Err(e) => issues.push(Issue::InvalidTorrent {
+                sidecars: sidecars(),
+                path,
+                content: c,
+                error: e.to_string(),
+            }),
+        }
+    }
+    Ok(())
+}
+
+fn check_content(
+    root: &Path,
+    content: &Path,
+    dir: &Path,
+    issues: &mut Vec<Issue>,
+) -> Result<(), Error> {
+    for dir_entry in fs::read_dir(dir)? {
+        let path = dir_entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let name = match path.file_name() {
+            Some(n) => n.to_string_lossy().to_string(),
+            None => continue,
+        };
+        if let Some(h) = name.strip_prefix('.')
+            && is_info_hash(h)
+            && dir == content
+        {
+            issues.push(Issue::OrphanTmp(path))
+        } else if is_info_hash(&name)
+            && !Layout::of(content, dir)
+                .dir(root, &name)
+                .join(format!("{name}.{E}"))
+                .is_file()
+        {
+            issues.push(Issue::OrphanContent(path))
+        }
+    }
+    Ok(())
+}
+
+/// Find the blobs without references, and the blob references to the missing content,
+/// or to the content discarded on repair of the `issues` found before
+fn check_refs(content: &Path, blobs: &Path, issues: &mut Vec<Issue>) -> Result<(), Error> {
+    let discarded = issues
+        .iter()
+        .filter_map(|i| match i {
+            Issue::OrphanContent(c)
+            | Issue::InvalidTorrent { content: c, .. }
+            | Issue::HashMismatch { content: c, .. } => c.file_name(),
+            _ => None,
+        })
+        .map(|n| n.to_string_lossy().to_string())
+        .collect::<HashSet<String>>();
+    for shard in fs::read_dir(blobs)? {
+        let shard = shard?.path();
+        if !shard.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&shard)? {
+            let path = entry?.path();
+            if path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+            {
+                continue; // tmp data
+            }
+            if path.extension().is_none() {
+                if !path.with_extension(R).exists() {
+                    issues.push(Issue::OrphanBlob(path))
+                }
+                continue;
+            }
+            if path.extension().is_some_and(|e| e != R) {
+                continue; // lock
+            }
+            for reference in fs::read_to_string(&path)?.lines() {
+                let is_valid = reference.split_once('/').is_some_and(|(h, p)| {
+                    !discarded.contains(h)
+                        && Layout::find(content, h, "").is_some_and(|d| d.join(p).is_file())
+                });
+                if !is_valid && !reference.is_empty() {
+                    issues.push(Issue::OrphanRef {
+                        path: path.clone(),
+                        reference: reference.to_string(),
+                    })
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Drop the `reference` from the blob `.refs` file at `path`,
+/// remove the blob if it is not in use anymore
+fn release(path: &Path, reference: &str) -> Result<(), Error> {
+    let refs = fs::read_to_string(path)?
+        .lines()
+        .filter(|l| !l.is_empty() && *l != reference)
+        .map(|l| l.to_string())
+        .collect::<Vec<String>>();
+    if refs.is_empty() {
+        let blob = path.with_extension("");
+        if blob.exists() {
+            fs::remove_file(blob)?
+        }
+        return fs::remove_file(path);
+    }
+    let t = path.with_extension("refs.tmp");
+    let mut f = fs::File::create(&t)?;
+    for r in refs {
+        writeln!(f, "{r}")?
+    }
+    f.sync_all()?;
+    fs::rename(t, path)
+}
+
+fn discard(path: &Path, repair: &Repair) -> Result<(), Error> {
+    match repair {
+        Repair::Delete => {
+            if path.is_dir() {
+                fs::remove_dir_all(path)
+            } else {
+                fs::remove_file(path)
+            }
+        }
+        Repair::Quarantine(dir) => {
+            let mut target = dir.to_path_buf();
+            target.push(path.file_name().unwrap());
+            if target.exists() {
+                return Err(Error::other(format!(
+                    "quarantine target `{}` already exists",
+                    target.to_string_lossy()
+                )));
+            }
+            fs::rename(path, target)
+        }
+    }
+}
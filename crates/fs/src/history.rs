@@ -0,0 +1,223 @@
+//! Swarm statistics time series, recorded by the crawler from the full-scrape sources
+//!
+//! * every info-hash has own `{root}/.history/{ab}/{abcd..}` file of fixed-size records,
+//!   ordered by time: `time`, `seeders`, `leechers`, `downloaded` (`u32` LE each)
+
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{Error, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Sample {
+    pub time: DateTime<Utc>,
+    pub seeders: u32,
+    pub leechers: u32,
+    pub downloaded: u32,
+}
+
+impl Sample {
+    fn to_bytes(self) -> [u8; L] {
+        let mut b = [0; L];
+        b[0..4].copy_from_slice(&(self.time.timestamp() as u32).to_le_bytes());
+        b[4..8].copy_from_slice(&self.seeders.to_le_bytes());
+        b[8..12].copy_from_slice(&self.leechers.to_le_bytes());
+        b[12..16].copy_from_slice(&self.downloaded.to_le_bytes());
+        b
+    }
+
+    fn from_bytes(b: &[u8]) -> Self {
+        let u = |i: usize| u32::from_le_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]]);
+        Self {
+            time: DateTime::from_timestamp(u(0) as i64, 0).unwrap_or_default(),
+            seeders: u(4),
+            leechers: u(8),
+            downloaded: u(12),
+        }
+    }
+
+    /// Compare the swarm values only
+    fn is_same(&self, other: &Self) -> bool {
+        self.seeders == other.seeders
+            && self.leechers == other.leechers
+            && self.downloaded == other.downloaded
+    }
+}
+
+pub struct History {
+    /// Last records by info-hash, to not read the series file on every `push`
+    last: Mutex<HashMap<String, Sample>>,
+    root: PathBuf,
+}
+
+impl History {
+    // Constructors
+
+    /// Init history in the storage `root`, create its directory if not exists
+    pub fn init(root: &Path) -> Result<Self, Error> {
+        let mut p = PathBuf::from(root);
+        p.push(D);
+        if !p.exists() {
+            fs::create_dir(&p)?
+        }
+        Ok(Self {
+            last: Mutex::new(HashMap::new()),
+            root: p,
+        })
+    }
+
+    /// Open existing history in the storage `root` (for reading)
+    pub fn open(root: &Path) -> Self {
+        let mut p = PathBuf::from(root);
+        p.push(D);
+        Self {
+            last: Mutex::new(HashMap::new()),
+            root: p,
+        }
+    }
+
+    // Actions
+
+    /// Append `sample` to the `info_hash` series
+    /// * skip if the swarm is not changed since the last sample,
+    ///   or the last sample is younger than `interval` seconds
+    /// * the last sample is read from the file once, then kept in memory until `compact`
+    ///
+    /// Returns `true` if recorded
+    pub fn push(&self, info_hash: &str, sample: Sample, interval: u64) -> Result<bool, Error> {
+        let p = self.path(info_hash);
+        let mut cache = self.last.lock().unwrap();
+        let l = match cache.get(info_hash) {
+            Some(l) => Some(*l),
+            None => last(&p)?,
+        };
+        if let Some(l) = l
+            && (l.is_same(&sample) || (sample.time - l.time).num_seconds() < interval as i64)
+        {
+            cache.insert(info_hash.to_string(), l);
+            return Ok(false);
+        }
+        fs::create_dir_all(p.parent().unwrap())?;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&p)?
+            .write_all(&sample.to_bytes())?;
+        cache.insert(info_hash.to_string(), sample);
+        Ok(true)
+    }
+
+    /// Downsample records older than `raw_seconds` to the daily maximum,
+    /// remove records older than `retention_seconds`
+    /// * the caller is responsible to run it by one worker at once (e.g. with the lease)
+    pub fn compact(&self, raw_seconds: u64, retention_seconds: u64) -> Result<(), Error> {
+        // forget the last records of the compacted and delisted series
+        self.last.lock().unwrap().clear();
+        if !self.root.exists() {
+            return Ok(());
+        }
+        let now = Utc::now();
+        for shard in fs::read_dir(&self.root)? {
+            let shard = shard?.path();
+            if !shard.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(&shard)? {
+                let p = entry?.path();
+                if p.extension().is_some() {
+                    continue; // tmp, `.{name}.{pid}.tmp`
+                }
+                let samples = read(&p)?;
+                let mut b: Vec<Sample> = Vec::with_capacity(samples.len());
+                for s in samples {
+                    let age = (now - s.time).num_seconds();
+                    if age > retention_seconds as i64 {
+                        continue;
+                    }
+                    if age > raw_seconds as i64
+                        && let Some(l) = b.last_mut()
+                        && l.time.date_naive() == s.time.date_naive()
+                    {
+                        l.seeders = l.seeders.max(s.seeders);
+                        l.leechers = l.leechers.max(s.leechers);
+                        l.downloaded = l.downloaded.max(s.downloaded);
+                        continue;
+                    }
+                    b.push(s)
+                }
+                if b.is_empty() {
+                    fs::remove_file(&p)?;
+                    continue;
+                }
+                let t = shard.join(format!(
+                    ".{}.{}.tmp",
+                    p.file_name().unwrap().to_string_lossy(),
+                    std::process::id()
+                ));
+                fs::write(&t, b.iter().flat_map(|s| s.to_bytes()).collect::<Vec<u8>>())?;
+                fs::rename(t, p)?
+            }
+        }
+        Ok(())
+    }
+
+    // Getters
+
+    /// Get all records of the `info_hash`, ordered by time
+    pub fn get(&self, info_hash: &str) -> Result<Vec<Sample>, Error> {
+        let p = self.path(info_hash);
+        if !p.exists() {
+            return Ok(Vec::new());
+        }
+        read(&p)
+    }
+
+    /// Get the most recent record of the `info_hash`
+    pub fn last(&self, info_hash: &str) -> Result<Option<Sample>, Error> {
+        last(&self.path(info_hash))
+    }
+
+    // Helpers
+
+    fn path(&self, info_hash: &str) -> PathBuf {
+        let mut p = PathBuf::from(&self.root);
+        p.push(info_hash.get(..2).unwrap_or_default());
+        p.push(info_hash);
+        p
+    }
+}
+
+// Local members
+
+/// History directory name in the storage root
+const D: &str = ".history";
+
+/// Record length
+const L: usize = 16;
+
+fn read(path: &Path) -> Result<Vec<Sample>, Error> {
+    Ok(fs::read(path)?
+        .chunks_exact(L)
+        .map(Sample::from_bytes)
+        .collect())
+}
+
+fn last(path: &Path) -> Result<Option<Sample>, Error> {
+    use std::io::{Read, Seek, SeekFrom};
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut f = fs::File::open(path)?;
+    let len = f.metadata()?.len();
+    if len < L as u64 {
+        return Ok(None);
+    }
+    f.seek(SeekFrom::Start(len - len % L as u64 - L as u64))?;
+    let mut b = [0; L];
+    f.read_exact(&mut b)?;
+    Ok(Some(Sample::from_bytes(&b)))
+}
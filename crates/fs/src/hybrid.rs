@@ -0,0 +1,121 @@
+//! BitTorrent v2 (BEP 52) info-hashes of the hybrid torrents
+//!
+//! * the resolver (`librqbit`) supports v1 only, so the hybrid torrents are stored by the v1
+//!   info-hash, and the v2 one is mapped to it by the alias
+//! * `{root}/.btmh/{info_hash_v2}` alias contains the v1 info-hash, the name is the v2 info-hash
+//!   truncated to 20 bytes, as it is listed by the trackers and used by the peer protocol
+//! * v2-only torrents are not supported
+
+use librqbit_core::{
+    Id20,
+    hash_id::Id32,
+    torrent_metainfo::{ParsedTorrent, TorrentMetaV1Borrowed, torrent_from_bytes_ext},
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    io::Error,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Info-hashes of the torrent
+pub struct InfoHashes {
+    /// SHA-1 of the `info` dictionary, the storage key
+    pub v1: Id20,
+    /// SHA-256 of the `info` dictionary, if the torrent is hybrid (`meta version` 2)
+    pub v2: Option<Id32>,
+}
+
+impl InfoHashes {
+    // Constructors
+
+    pub fn from_bytes(torrent_bytes: &[u8]) -> Result<Self, String> {
+        #[derive(Deserialize)]
+        struct Info {
+            #[serde(rename = "meta version")]
+            meta_version: Option<u32>,
+        }
+        let ParsedTorrent { meta, info_bytes } = torrent_from_bytes_ext(torrent_bytes)
+            .map_err(|e| format!("could not parse metadata: {e}"))?;
+        let meta: TorrentMetaV1Borrowed = meta;
+        let info: &[u8] = info_bytes.as_ref();
+        Ok(Self {
+            v1: meta.info_hash,
+            v2: librqbit_bencode::from_bytes::<Info>(info)
+                .ok()
+                .and_then(|i| i.meta_version)
+                .filter(|v| *v == 2)
+                .map(|_| Id32::new(Sha256::digest(info).into())),
+        })
+    }
+}
+
+/// Truncate the v2 info-hash to 20 bytes, as used by the trackers and the peer protocol
+pub fn truncate(v2: &Id32) -> Id20 {
+    let mut b = [0; 20];
+    b.copy_from_slice(&v2.0[..20]);
+    Id20::new(b)
+}
+
+/// Index of the v2 info-hashes to the v1 storage keys
+pub struct Aliases(PathBuf);
+
+impl Aliases {
+    // Constructors
+
+    pub fn open(root: &Path) -> Self {
+        Self(root.join(D))
+    }
+
+    // Actions
+
+    pub fn insert(&self, v2: &Id32, v1: &Id20) -> Result<(), Error> {
+        fs::create_dir_all(&self.0)?;
+        fs::write(self.0.join(truncate(v2).as_string()), v1.as_string())
+    }
+
+    pub fn remove(&self, v2: &Id32) -> Result<(), Error> {
+        let p = self.0.join(truncate(v2).as_string());
+        if p.exists() {
+            fs::remove_file(p)?
+        }
+        Ok(())
+    }
+
+    // Getters
+
+    /// Get the v1 info-hash for the full (64 chars) or truncated (40 chars) v2 `info_hash`
+    /// * the full one is not compared, use `InfoHashes` of the torrent bytes to make sure
+    pub fn get(&self, info_hash: &str) -> Option<Id20> {
+        let t = match info_hash.len() {
+            40 => info_hash,
+            64 => info_hash.get(..40)?,
+            _ => return None,
+        };
+        let h = Id20::from_str(t).ok()?;
+        Id20::from_str(fs::read_to_string(self.0.join(h.as_string())).ok()?.trim()).ok()
+    }
+
+    /// Get the alias file paths, with the v1 info-hash they point to
+    pub fn list(&self) -> Result<Vec<(PathBuf, String)>, Error> {
+        let mut aliases = Vec::new();
+        if !self.0.is_dir() {
+            return Ok(aliases);
+        }
+        for entry in fs::read_dir(&self.0)? {
+            let path = entry?.path();
+            if path.is_file() {
+                let v1 = fs::read_to_string(&path)?.trim().to_lowercase();
+                aliases.push((path, v1))
+            }
+        }
+        Ok(aliases)
+    }
+}
+
+// Local members
+
+/// Alias directory name in the storage root
+const D: &str = ".btmh";
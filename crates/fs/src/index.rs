@@ -0,0 +1,454 @@
+//! Persistent metadata index of the committed torrents,
+//! to list and search them without parsing the `.torrent` files and their sidecars
+//!
+//! * `{root}/.index/records` snapshot contains one JSON record per line
+//! * `{root}/.index/{info_hash}` journal entries are written by the crawler on commit,
+//!   they override the snapshot; the empty entry marks the removed torrent
+//! * `{root}/.index/swarm` snapshot contains the last swarm stats reported by the full-scrape
+//!   sources, one `{info_hash} {seeders} {leechers}` line per info-hash, replaced by the crawler
+//! * every entry is written aside and renamed in place, so the index can be shared between workers
+//! * the journal is merged into the snapshot by `Index::compact`, the whole index is rebuilt
+//!   from the `.torrent` files by `Index::rebuild` (`btracker-fs reindex`)
+
+use crate::{
+    hybrid::InfoHashes,
+    layout::{Layout, is_info_hash},
+    meta::{self, Meta},
+};
+use chrono::{DateTime, Utc};
+use librqbit_core::{
+    Id20,
+    torrent_metainfo::{TorrentMetaV1Owned, torrent_from_bytes},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::SystemTime,
+};
+
+/// Parsed metadata of the committed torrent
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Record {
+    pub info_hash: String,
+    /// v2 info-hash of the hybrid torrent
+    pub info_hash_v2: Option<String>,
+    pub name: Option<String>,
+    pub comment: Option<String>,
+    pub created_by: Option<String>,
+    /// Torrent creation date
+    pub created: Option<DateTime<Utc>>,
+    pub publisher: Option<String>,
+    pub publisher_url: Option<String>,
+    /// Trackers of the `announce` and `announce-list` fields
+    #[serde(default)]
+    pub announce: Vec<String>,
+    #[serde(default)]
+    pub is_private: bool,
+    /// Total size of the content
+    pub size: u64,
+    /// Files of the multi-file torrent
+    pub files: Option<Vec<File>>,
+    /// Resolve time from the crawler sidecar or the `.torrent` file modification time
+    pub time: DateTime<Utc>,
+    /// Names of the full-scrape sources listed the info-hash, from the crawler sidecar
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// Fake release score, from the crawler sidecar
+    pub spam: Option<u32>,
+    /// Time since the torrent is delisted or dead, from the crawler sidecar
+    pub inactive_since: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct File {
+    /// `None` on the filename decode error
+    pub path: Option<PathBuf>,
+    pub length: u64,
+}
+
+impl Record {
+    // Constructors
+
+    pub fn from_bytes(torrent_bytes: &[u8], time: DateTime<Utc>) -> Result<Self, String> {
+        let i: TorrentMetaV1Owned = torrent_from_bytes(torrent_bytes).map_err(|e| e.to_string())?;
+        let mut announce = Vec::new();
+        for a in i
+            .announce
+            .iter()
+            .chain(i.announce_list.iter().flatten())
+            .map(|a| a.to_string())
+        {
+            if !announce.contains(&a) {
+                announce.push(a)
+            }
+        }
+        Ok(Self {
+            info_hash: i.info_hash.as_string(),
+            info_hash_v2: InfoHashes::from_bytes(torrent_bytes)?
+                .v2
+                .map(|h| h.as_string()),
+            name: i.info.name.map(|n| n.to_string()),
+            comment: i.comment.map(|c| c.to_string()),
+            created_by: i.created_by.map(|c| c.to_string()),
+            created: i
+                .creation_date
+                .and_then(|t| DateTime::from_timestamp(t as i64, 0)),
+            publisher: i.publisher.map(|p| p.to_string()),
+            publisher_url: i.publisher_url.map(|u| u.to_string()),
+            announce,
+            is_private: i.info.private,
+            size: i.info.length.unwrap_or_default()
+                + i.info
+                    .files
+                    .as_ref()
+                    .map(|files| files.iter().map(|f| f.length).sum::<u64>())
+                    .unwrap_or_default(),
+            files: i.info.files.map(|files| {
+                files
+                    .iter()
+                    .map(|f| {
+                        let mut p = PathBuf::new();
+                        File {
+                            path: f.full_path(&mut p).ok().map(|_| p),
+                            length: f.length,
+                        }
+                    })
+                    .collect()
+            }),
+            time,
+            sources: Vec::new(),
+            spam: None,
+            inactive_since: None,
+        })
+    }
+
+    /// Copy the listing state of the crawler `meta` sidecar
+    pub fn with_meta(mut self, meta: &Meta) -> Self {
+        self.sources = meta.sources.clone();
+        self.spam = meta.spam.as_ref().map(|s| s.score);
+        self.inactive_since = meta.inactive_since();
+        self
+    }
+
+    // Getters
+
+    pub fn id20(&self) -> Option<Id20> {
+        Id20::from_str(&self.info_hash).ok()
+    }
+
+    /// Check the info-hash was listed by the full-scrape `source` of given name
+    pub fn is_listed_by(&self, source: &str) -> bool {
+        self.sources.iter().any(|s| s == source)
+    }
+
+    /// Check the spam score reaches the `threshold`
+    pub fn is_spam(&self, threshold: u32) -> bool {
+        self.spam.is_some_and(|s| s >= threshold)
+    }
+}
+
+/// Last swarm stats of the info-hash, reported by the full-scrape sources
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Swarm {
+    pub seeders: u32,
+    pub leechers: u32,
+}
+
+/// Journal entry of the info-hash
+pub enum Entry {
+    Record(Box<Record>),
+    Removed,
+}
+
+/// Changed file of the index directory
+pub enum Change {
+    /// The snapshot is replaced (compacted or rebuilt)
+    Snapshot,
+    /// The journal entry of the info-hash is written
+    Journal(String),
+    /// The swarm stats snapshot is replaced
+    Swarm,
+}
+
+impl Change {
+    /// Detect the change by the `path` reported by the filesystem watcher
+    /// * temporary files are skipped
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy();
+        if name == S {
+            Some(Self::Snapshot)
+        } else if name == W {
+            Some(Self::Swarm)
+        } else if is_info_hash(&name) {
+            Some(Self::Journal(name.to_string()))
+        } else {
+            None
+        }
+    }
+}
+
+pub struct Index(PathBuf);
+
+impl Index {
+    // Constructors
+
+    /// Init the index in the storage `root`, create its directory if not exists
+    pub fn init(root: &Path) -> Result<Self, Error> {
+        let i = Self::open(root);
+        if !i.0.exists() {
+            fs::create_dir(&i.0)?
+        }
+        Ok(i)
+    }
+
+    /// Open existing index in the storage `root` (for reading)
+    pub fn open(root: &Path) -> Self {
+        Self(root.join(D))
+    }
+
+    /// Build the index from the `.torrent` files of the storage `root` (any `Layout`)
+    /// * the journal written before the rebuild is discarded
+    /// * invalid torrents are skipped (see `fsck`)
+    /// * returns the number of indexed torrents
+    pub fn rebuild(root: &Path) -> Result<usize, Error> {
+        let index = Self::init(root)?;
+        let start = SystemTime::now();
+        let mut records = Vec::new();
+        for dir in Layout::dirs(root)? {
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                let path = entry.path();
+                if !path.is_file()
+                    || path.extension().is_none_or(|e| e != "torrent")
+                    || path
+                        .file_stem()
+                        .is_none_or(|s| !is_info_hash(&s.to_string_lossy()))
+                {
+                    continue;
+                }
+                let meta = Meta::read(&path.with_extension(meta::E)).ok();
+                let time = meta
+                    .as_ref()
+                    .and_then(|m| m.resolved_at)
+                    .unwrap_or(entry.metadata()?.modified()?.into());
+                if let Ok(r) = Record::from_bytes(&fs::read(&path)?, time) {
+                    records.push(match meta {
+                        Some(ref m) => r.with_meta(m),
+                        None => r,
+                    })
+                }
+            }
+        }
+        index.write_snapshot(records.iter())?;
+        for entry in fs::read_dir(&index.0)? {
+            let entry = entry?;
+            if entry.file_name() != S && entry.metadata()?.modified()? < start {
+                fs::remove_file(entry.path())?
+            }
+        }
+        Ok(records.len())
+    }
+
+    // Actions
+
+    /// Add or replace the `record` in the journal
+    pub fn insert(&self, record: &Record) -> Result<(), Error> {
+        self.write_entry(
+            &record.info_hash,
+            &serde_json::to_vec(record).map_err(Error::other)?,
+        )
+    }
+
+    /// Mark the `info_hash` removed in the journal
+    pub fn remove(&self, info_hash: &str) -> Result<(), Error> {
+        self.write_entry(info_hash, &[])
+    }
+
+    /// Replace the swarm stats snapshot
+    pub fn write_swarm(&self, swarm: impl Iterator<Item = (String, Swarm)>) -> Result<(), Error> {
+        let mut data = String::new();
+        for (info_hash, s) in swarm {
+            data.push_str(&format!("{info_hash} {} {}\n", s.seeders, s.leechers))
+        }
+        self.write_entry(W, data.as_bytes())
+    }
+
+    /// Merge the journal into the snapshot
+    /// * make sure it is not running by another worker at the same time
+    /// * returns the number of merged entries
+    pub fn compact(&self) -> Result<usize, Error> {
+        // move the journal entries aside first:
+        // entries written in the middle are kept for the next time
+        let mut aside = Vec::new();
+        for entry in fs::read_dir(&self.0)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if is_info_hash(&name) {
+                let p = self.0.join(format!(".{name}.{}.{M}", std::process::id()));
+                fs::rename(entry.path(), &p)?;
+                aside.push((name, p))
+            } else if name.ends_with(&format!(".{M}")) {
+                // left by the interrupted compaction
+                if let Some(h) = name.get(1..41) {
+                    aside.push((h.to_string(), entry.path()))
+                }
+            }
+        }
+        if aside.is_empty() {
+            return Ok(0);
+        }
+        aside.sort_by_key(|(_, p)| p.metadata().and_then(|m| m.modified()).ok());
+        let mut records = self.read_snapshot()?;
+        for (info_hash, path) in &aside {
+            apply(&mut records, info_hash, &fs::read(path)?)?
+        }
+        self.write_snapshot(records.values())?;
+        for (_, path) in &aside {
+            fs::remove_file(path)?
+        }
+        Ok(aside.len())
+    }
+
+    // Getters
+
+    /// Read all records of the index, unordered
+    pub fn read(&self) -> Result<Vec<Record>, Error> {
+        let mut records = self.read_snapshot()?;
+        for entry in fs::read_dir(&self.0)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if is_info_hash(&name) {
+                match fs::read(entry.path()) {
+                    Ok(data) => apply(&mut records, &name, &data)?,
+                    // merged in the middle
+                    Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(records.into_values().collect())
+    }
+
+    /// Read the journal entry of the `info_hash`
+    /// * returns `None` if not exists (e.g. merged into the snapshot)
+    pub fn entry(&self, info_hash: &str) -> Result<Option<Entry>, Error> {
+        match fs::read(self.0.join(info_hash)) {
+            Ok(data) if data.is_empty() => Ok(Some(Entry::Removed)),
+            Ok(data) => Ok(Some(Entry::Record(
+                serde_json::from_slice(&data).map_err(Error::other)?,
+            ))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Read the swarm stats snapshot
+    /// * returns empty map if not exists (e.g. not written by the crawler yet)
+    pub fn swarm(&self) -> Result<HashMap<String, Swarm>, Error> {
+        let data = match fs::read_to_string(self.0.join(W)) {
+            Ok(d) => d,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e),
+        };
+        let mut swarm = HashMap::new();
+        for line in data.lines() {
+            let mut v = line.split(' ');
+            if let (Some(h), Some(s), Some(l)) = (v.next(), v.next(), v.next()) {
+                swarm.insert(
+                    h.to_string(),
+                    Swarm {
+                        seeders: s.parse().map_err(Error::other)?,
+                        leechers: l.parse().map_err(Error::other)?,
+                    },
+                );
+            }
+        }
+        Ok(swarm)
+    }
+
+    /// Get the index directory location, e.g. to watch its changes
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Count the journal entries, not merged into the snapshot yet
+    pub fn journal(&self) -> Result<usize, Error> {
+        let mut n = 0;
+        for entry in fs::read_dir(&self.0)? {
+            if is_info_hash(&entry?.file_name().to_string_lossy()) {
+                n += 1
+            }
+        }
+        Ok(n)
+    }
+
+    // Tools
+
+    fn read_snapshot(&self) -> Result<HashMap<String, Record>, Error> {
+        let data = match fs::read_to_string(self.0.join(S)) {
+            Ok(d) => d,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Err(Error::new(
+                    ErrorKind::NotFound,
+                    "metadata index not found, run `btracker-fs reindex`",
+                ));
+            }
+            Err(e) => return Err(e),
+        };
+        let mut records = HashMap::new();
+        for line in data.lines().filter(|l| !l.is_empty()) {
+            let r: Record = serde_json::from_str(line).map_err(Error::other)?;
+            records.insert(r.info_hash.clone(), r);
+        }
+        Ok(records)
+    }
+
+    fn write_snapshot<'a>(&self, records: impl Iterator<Item = &'a Record>) -> Result<(), Error> {
+        let mut data = Vec::new();
+        for r in records {
+            serde_json::to_writer(&mut data, r).map_err(Error::other)?;
+            data.push(b'\n')
+        }
+        self.write_entry(S, &data)
+    }
+
+    fn write_entry(&self, name: &str, data: &[u8]) -> Result<(), Error> {
+        let part = self.0.join(format!(".{name}.{}.{P}", std::process::id()));
+        fs::write(&part, data)?;
+        fs::rename(&part, self.0.join(name))
+    }
+}
+
+// Local members
+
+/// Index directory name in the storage root
+const D: &str = ".index";
+
+/// Snapshot filename
+const S: &str = "records";
+
+/// Swarm stats snapshot filename
+const W: &str = "swarm";
+
+/// Partially written file extension
+const P: &str = "part";
+
+/// Journal entry moved aside by the compaction
+const M: &str = "merge";
+
+/// Apply the journal entry `data` of the `info_hash` to the `records`
+fn apply(records: &mut HashMap<String, Record>, info_hash: &str, data: &[u8]) -> Result<(), Error> {
+    if data.is_empty() {
+        records.remove(info_hash);
+    } else {
+        records.insert(
+            info_hash.to_string(),
+            serde_json::from_slice(data).map_err(Error::other)?,
+        );
+    }
+    Ok(())
+}
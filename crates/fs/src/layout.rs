@@ -0,0 +1,164 @@
+//! Directory layout of the torrent storage root
+//!
+//! * `Flat`: `{root}/abcd...torrent`, `{root}/abcd.../`
+//! * `Sharded`: `{root}/ab/cd/abcd...torrent`, `{root}/ab/cd/abcd.../`
+
+use std::{
+    fs,
+    io::Error,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Layout {
+    #[default]
+    Flat,
+    Sharded,
+}
+
+impl Layout {
+    /// Build the absolute path to the directory of the given `info_hash` entries
+    pub fn dir(&self, root: &Path, info_hash: &str) -> PathBuf {
+        let mut p = PathBuf::from(root);
+        if *self == Self::Sharded
+            && let Some(a) = info_hash.get(..2)
+            && let Some(b) = info_hash.get(2..4)
+        {
+            p.push(a);
+            p.push(b);
+        }
+        p
+    }
+
+    /// Find existing location of the `info_hash` entry with given `suffix` (e.g. `.torrent`)
+    /// * check all layouts, starting from sharded
+    pub fn find(root: &Path, info_hash: &str, suffix: &str) -> Option<PathBuf> {
+        for l in [Self::Sharded, Self::Flat] {
+            let mut p = l.dir(root, info_hash);
+            p.push(format!("{info_hash}{suffix}"));
+            if p.exists() {
+                return Some(p);
+            }
+        }
+        None
+    }
+
+    /// Detect the layout of the `dir` returned by `Layout::dirs` for the `root`
+    pub fn of(root: &Path, dir: &Path) -> Self {
+        if dir == root {
+            Self::Flat
+        } else {
+            Self::Sharded
+        }
+    }
+
+    /// Return shard directories of the `root`, including the `root` itself
+    pub fn dirs(root: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut dirs = vec![root.to_path_buf()];
+        for a in fs::read_dir(root)? {
+            let a = a?.path();
+            if !is_shard(&a) {
+                continue;
+            }
+            for b in fs::read_dir(&a)? {
+                let b = b?.path();
+                if is_shard(&b) {
+                    dirs.push(b)
+                }
+            }
+        }
+        Ok(dirs)
+    }
+}
+
+impl FromStr for Layout {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flat" => Ok(Self::Flat),
+            "sharded" => Ok(Self::Sharded),
+            _ => Err(format!("unknown layout `{s}`")),
+        }
+    }
+}
+
+/// Move all the `root` entries to the given `layout`
+/// * `content` root of the preloaded files, if kept apart from the `root`
+/// * returns the count of moved torrents
+/// * make sure the crawler is not running
+pub fn migrate(root: &Path, content: Option<&Path>, layout: Layout) -> Result<usize, Error> {
+    let content = content.unwrap_or(root);
+    let mut moved = 0;
+    for dir in Layout::dirs(root)? {
+        let mut torrents = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_file()
+                && path.extension().is_some_and(|e| e == "torrent")
+                && let Some(s) = path.file_stem()
+                && is_info_hash(&s.to_string_lossy())
+            {
+                torrents.push(s.to_string_lossy().to_string())
+            }
+        }
+        let current = Layout::of(root, &dir);
+        for info_hash in torrents {
+            let target = layout.dir(root, &info_hash);
+            if target == dir {
+                continue;
+            }
+            // move the preloaded content first
+            let from = current.dir(content, &info_hash).join(&info_hash);
+            if from.exists() {
+                let to = layout.dir(content, &info_hash);
+                fs::create_dir_all(&to)?;
+                fs::rename(from, to.join(&info_hash))?
+            }
+            fs::create_dir_all(&target)?;
+            // move the torrent file last, as it is the commit point
+            for suffix in [".json", ".verify", ".torrent"] {
+                let name = format!("{info_hash}{suffix}");
+                let from = dir.join(&name);
+                if from.exists() {
+                    fs::rename(from, target.join(name))?
+                }
+            }
+            moved += 1
+        }
+        // cleanup empty shards
+        cleanup(root, &dir)?;
+        if content != root
+            && let Ok(s) = dir.strip_prefix(root)
+        {
+            cleanup(content, &content.join(s))?
+        }
+    }
+    Ok(moved)
+}
+
+/// Remove the empty shard `dir` of the `root` with its empty parent
+fn cleanup(root: &Path, dir: &Path) -> Result<(), Error> {
+    if dir != root && dir.is_dir() && fs::read_dir(dir)?.next().is_none() {
+        fs::remove_dir(dir)?;
+        if let Some(parent) = dir.parent()
+            && fs::read_dir(parent)?.next().is_none()
+        {
+            fs::remove_dir(parent)?
+        }
+    }
+    Ok(())
+}
+
+/// Check the `value` is the hex-encoded v1 info-hash (in any case)
+pub(crate) fn is_info_hash(value: &str) -> bool {
+    value.len() == 40 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn is_shard(path: &Path) -> bool {
+    path.is_dir()
+        && path.file_name().is_some_and(|n| {
+            let n = n.to_string_lossy();
+            n.len() == 2 && n.chars().all(|c| c.is_ascii_hexdigit())
+        })
+}
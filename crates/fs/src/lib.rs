@@ -3,3 +3,23 @@ pub mod public;
 
 #[cfg(feature = "crawler")]
 pub mod crawler;
+
+pub mod layout;
+
+#[cfg(any(feature = "crawler", feature = "public"))]
+pub mod history;
+
+#[cfg(any(feature = "crawler", feature = "public", feature = "fsck"))]
+pub mod hybrid;
+
+#[cfg(any(feature = "crawler", feature = "public", feature = "fsck"))]
+pub mod index;
+
+#[cfg(any(feature = "crawler", feature = "public", feature = "fsck"))]
+pub mod meta;
+
+#[cfg(any(feature = "crawler", feature = "public"))]
+pub mod verify;
+
+#[cfg(feature = "fsck")]
+pub mod fsck;
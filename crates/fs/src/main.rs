@@ -0,0 +1,150 @@
+use btracker_fs::{
+    fsck::{self, Issue, Repair},
+    index::Index,
+    layout::{self, Layout},
+};
+use clap::{Parser, Subcommand};
+use std::{path::PathBuf, process::ExitCode};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Opt {
+    /// Storage root (e.g. `preload.path` of the `btracker-crawler`)
+    #[arg(long, short)]
+    root: PathBuf,
+
+    /// Root of the preloaded content, if kept apart (e.g. `preload.content_path` of the `btracker-crawler`)
+    #[arg(long, short)]
+    content: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Report storage issues
+    Fsck {
+        /// Delete invalid entries
+        #[arg(long, default_value_t = false)]
+        repair: bool,
+
+        /// Move invalid entries to given directory instead of delete
+        /// * temporary data is removed anyway
+        #[arg(long)]
+        quarantine: Option<PathBuf>,
+
+        /// Root of the shared blobs (e.g. `preload.blobs` of the `btracker-crawler`),
+        /// to check their references
+        #[arg(long)]
+        blobs: Option<PathBuf>,
+    },
+    /// Convert the storage to another layout in place
+    /// * make sure the `btracker-crawler` is not running
+    Migrate {
+        /// Target layout: `flat` or `sharded`
+        #[arg(long, default_value_t = String::from("sharded"))]
+        layout: String,
+    },
+    /// Rebuild the metadata index of the frontends from the `.torrent` files
+    /// * run it after the manual changes, or on the index read errors
+    Reindex,
+}
+
+fn main() -> ExitCode {
+    let opt = Opt::parse();
+    match opt.command {
+        Command::Fsck {
+            repair,
+            quarantine,
+            blobs,
+        } => {
+            let issues = match fsck::check(&opt.root, opt.content.as_deref(), blobs.as_deref()) {
+                Ok(issues) => issues,
+                Err(e) => {
+                    eprintln!("could not check `{}`: {e}", opt.root.to_string_lossy());
+                    return ExitCode::FAILURE;
+                }
+            };
+            let r = match quarantine {
+                Some(ref q) => {
+                    if !q.is_dir() {
+                        eprintln!("quarantine `{}` is not directory", q.to_string_lossy());
+                        return ExitCode::FAILURE;
+                    }
+                    Some(Repair::Quarantine(q))
+                }
+                None if repair => Some(Repair::Delete),
+                None => None,
+            };
+            let mut unresolved = 0;
+            let mut is_reindex = false;
+            for issue in issues.iter() {
+                match r {
+                    Some(ref r) => match issue.repair(r) {
+                        Ok(()) => {
+                            // drop the removed torrents from the metadata index
+                            is_reindex |= matches!(
+                                issue,
+                                Issue::InvalidTorrent { .. } | Issue::HashMismatch { .. }
+                            );
+                            println!("[repaired] {issue}")
+                        }
+                        Err(e) => {
+                            unresolved += 1;
+                            eprintln!("[failed] {issue}: {e}")
+                        }
+                    },
+                    None => {
+                        unresolved += 1;
+                        println!("{issue}")
+                    }
+                }
+            }
+            if is_reindex {
+                match Index::rebuild(&opt.root) {
+                    Ok(indexed) => println!("{indexed} torrents indexed."),
+                    Err(e) => {
+                        unresolved += 1;
+                        eprintln!("could not index `{}`: {e}", opt.root.to_string_lossy())
+                    }
+                }
+            }
+            println!("{} issues found, {} unresolved.", issues.len(), unresolved);
+            if unresolved > 0 {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        Command::Migrate { layout } => {
+            let l: Layout = match layout.parse() {
+                Ok(l) => l,
+                Err(e) => {
+                    eprintln!("{e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            match layout::migrate(&opt.root, opt.content.as_deref(), l) {
+                Ok(moved) => {
+                    println!("{moved} torrents moved to the `{layout}` layout.");
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("could not migrate `{}`: {e}", opt.root.to_string_lossy());
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Command::Reindex => match Index::rebuild(&opt.root) {
+            Ok(indexed) => {
+                println!("{indexed} torrents indexed.");
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("could not index `{}`: {e}", opt.root.to_string_lossy());
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
@@ -0,0 +1,122 @@
+//! Structured `{info_hash}.json` sidecar, written by the crawler on commit
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{fs, io::Error, path::Path};
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Meta {
+    /// Time when the info-hash was listed by the source first time
+    pub first_seen: Option<DateTime<Utc>>,
+    /// Time when the torrent was resolved and committed
+    pub resolved_at: Option<DateTime<Utc>>,
+    /// Names of the full-scrape sources listed the info-hash
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// Trackers used to discover the peers
+    #[serde(default)]
+    pub trackers: Vec<String>,
+    /// Number of peers used to resolve the torrent
+    #[serde(default)]
+    pub peers: usize,
+    /// Preload rule outcome for every file of the torrent
+    #[serde(default)]
+    pub preload: Vec<Preload>,
+    /// Preload errors (e.g. failed verification)
+    #[serde(default)]
+    pub errors: Vec<String>,
+    /// Time when the info-hash was not listed by the full-scrape sources anymore
+    pub delisted_at: Option<DateTime<Utc>>,
+    /// Time when the full-scrape sources reported no seeders for the info-hash
+    pub dead_at: Option<DateTime<Utc>>,
+    /// Time when the preloaded content was dropped by the lifecycle policy
+    pub dropped_at: Option<DateTime<Utc>>,
+    /// Fake release heuristics result
+    pub spam: Option<Spam>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Spam {
+    /// Score from 0 (clean) to 100
+    pub score: u32,
+    /// Names of the triggered rules
+    #[serde(default)]
+    pub rules: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Preload {
+    pub path: String,
+    pub outcome: Outcome,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// File match the preload rules
+    Keep,
+    /// `max_filecount` limit reached
+    SkipCount,
+    /// `max_filesize` limit reached
+    SkipSize,
+    /// Filename does not match `regex`
+    SkipRegex,
+}
+
+impl Meta {
+    // Constructors
+
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        serde_json::from_slice(&fs::read(path)?).map_err(Error::other)
+    }
+
+    // Getters
+
+    /// Get the time since the torrent is delisted or dead (the earliest one)
+    pub fn inactive_since(&self) -> Option<DateTime<Utc>> {
+        match (self.delisted_at, self.dead_at) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Get the lifecycle state label (`delisted` or `dead`) of the inactive torrent
+    pub fn inactive(&self) -> Option<&'static str> {
+        if self.delisted_at.is_some() {
+            Some("delisted")
+        } else if self.dead_at.is_some() {
+            Some("dead")
+        } else {
+            None
+        }
+    }
+
+    /// Check the info-hash was listed by the full-scrape `source` of given name
+    pub fn is_listed_by(&self, source: &str) -> bool {
+        self.sources.iter().any(|s| s == source)
+    }
+
+    /// Check the spam score reaches the `threshold`
+    pub fn is_spam(&self, threshold: u32) -> bool {
+        self.spam.as_ref().is_some_and(|s| s.score >= threshold)
+    }
+
+    // Actions
+
+    /// Write aside and rename in place, as the sidecar is read by the frontends and other workers
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let part = path.with_file_name(format!(
+            ".{}.{}.part",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            std::process::id()
+        ));
+        fs::write(
+            &part,
+            serde_json::to_vec_pretty(self).map_err(Error::other)?,
+        )?;
+        fs::rename(&part, path)
+    }
+}
+
+/// Sidecar file extension
+pub const E: &str = "json";
@@ -3,73 +3,278 @@
 //! * https://github.com/YGGverse/btracker
 //! * https://github.com/YGGverse/btracker-gemini
 
+pub mod query;
+
+mod search;
+
+use crate::{
+    history::{History, Sample},
+    hybrid::{Aliases, InfoHashes},
+    index::{self, Change, Index, Record, Swarm},
+    layout::Layout,
+    meta::{self, Meta},
+    verify::{self, Verification},
+};
 use chrono::{DateTime, Utc};
-use librqbit_core::Id20;
+use librqbit_core::{Id20, hash_id::Id32};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use query::Query;
+use search::Search;
 use std::{
+    collections::{HashMap, HashSet},
     fs,
     future::Future,
     io::Error,
     path::{Path, PathBuf},
     str::FromStr,
+    sync::{Arc, Mutex, RwLock},
     time::SystemTime,
 };
 
-#[derive(Clone, Debug, Default)]
+/// Sort key of the torrents list
+/// * torrents with equal keys are ordered by `Modified` (newest first), then by info-hash
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub enum Sort {
+    /// Resolve time
     #[default]
     Modified,
+    /// Torrent name, case insensitive
+    Name,
+    /// Total size of the content
+    Size,
+    /// Number of files
+    Files,
+    /// Torrent creation date, if defined
+    Created,
+    /// Last seeders count reported by the full-scrape sources
+    Seeders,
+    /// Last leechers count reported by the full-scrape sources
+    Leechers,
+    /// Search relevance score, or `Modified` if the query has no text terms
+    Relevance,
 }
 
-#[derive(Clone, Debug, Default)]
+impl Sort {
+    /// All variants, e.g. to build the frontend menu
+    pub const ALL: [Self; 8] = [
+        Self::Modified,
+        Self::Name,
+        Self::Size,
+        Self::Files,
+        Self::Created,
+        Self::Seeders,
+        Self::Leechers,
+        Self::Relevance,
+    ];
+
+    /// Natural order of the sort key (A to Z, greatest or newest first)
+    pub fn order(&self) -> Order {
+        match self {
+            Self::Name => Order::Asc,
+            _ => Order::Desc,
+        }
+    }
+}
+
+impl std::fmt::Display for Sort {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Modified => "modified",
+                Self::Name => "name",
+                Self::Size => "size",
+                Self::Files => "files",
+                Self::Created => "created",
+                Self::Seeders => "seeders",
+                Self::Leechers => "leechers",
+                Self::Relevance => "relevance",
+            }
+        )
+    }
+}
+
+impl FromStr for Sort {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|v| v.to_string() == s)
+            .ok_or_else(|| format!("unknown sort `{s}`"))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub enum Order {
     #[default]
     Asc,
     Desc,
 }
 
+impl std::fmt::Display for Order {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Asc => "asc",
+                Self::Desc => "desc",
+            }
+        )
+    }
+}
+
+impl FromStr for Order {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asc" => Ok(Self::Asc),
+            "desc" => Ok(Self::Desc),
+            _ => Err(format!("unknown order `{s}`")),
+        }
+    }
+}
+
 pub struct Torrent {
     pub bytes: Vec<u8>,
+    /// Crawler sidecar, if exists
+    pub meta: Option<Meta>,
+    /// Resolve time from the `meta` or the file modification time
     pub time: DateTime<Utc>,
 }
 
+impl Torrent {
+    fn read(path: &Path, modified: SystemTime) -> Result<Self, Error> {
+        let meta = Meta::read(&path.with_extension(meta::E)).ok();
+        Ok(Self {
+            bytes: fs::read(path)?,
+            time: meta
+                .as_ref()
+                .and_then(|m| m.resolved_at)
+                .unwrap_or(modified.into()),
+            meta,
+        })
+    }
+}
+
 pub struct Storage {
+    /// Root of the preloaded content, the same as `root` by default
+    content: PathBuf,
     default_capacity: usize,
     pub default_limit: usize,
+    /// Skip delisted or dead torrents (marked by the crawler lifecycle policy) from listing
+    hide_inactive: bool,
+    /// Skip torrents with the spam score reaching given threshold from listing
+    hide_spam: Option<u32>,
+    index: Index,
+    /// Index changes reported by the `watcher`, applied on the next read
+    pending: Arc<Mutex<Pending>>,
     root: PathBuf,
+    search: RwLock<Search>,
+    /// Last swarm stats of the `index`, to rank and sort the torrents
+    swarm: RwLock<HashMap<String, Swarm>>,
+    /// Keep the `index` watcher alive
+    _watcher: RecommendedWatcher,
 }
 
 impl Storage {
     // Constructors
 
+    /// * `content` root of the preloaded files, if kept apart from the metadata by the crawler
     pub fn init(
         root: &Path,
+        content: Option<&Path>,
         default_limit: usize,
         default_capacity: usize,
+        hide_inactive: bool,
+        hide_spam: Option<u32>,
     ) -> Result<Self, String> {
         if !root.is_dir() {
             return Err("Public root is not directory".into());
         }
+        let root = root.canonicalize().map_err(|e| e.to_string())?;
+        let index = Index::init(&root).map_err(|e| e.to_string())?;
+        // build the index of the storage created by the previous versions
+        if index.read().is_err() {
+            Index::rebuild(&root).map_err(|e| format!("Could not build metadata index: {e}"))?;
+        }
+        // watch before the build, to not miss the changes in the middle
+        let pending = Arc::new(Mutex::new(Pending::default()));
+        let mut watcher = notify::recommended_watcher({
+            let pending = pending.clone();
+            move |event: notify::Result<notify::Event>| {
+                let mut p = pending.lock().unwrap();
+                match event {
+                    Ok(e) if !e.need_rescan() => {
+                        for change in e.paths.iter().filter_map(|path| Change::from_path(path)) {
+                            match change {
+                                Change::Snapshot => p.reload = true,
+                                Change::Journal(h) => {
+                                    p.journal.insert(h);
+                                }
+                                Change::Swarm => p.swarm = true,
+                            }
+                        }
+                    }
+                    // events may be lost
+                    _ => p.reload = true,
+                }
+            }
+        })
+        .map_err(|e| e.to_string())?;
+        watcher
+            .watch(index.path(), RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Could not watch metadata index: {e}"))?;
+        let search = RwLock::new(Search::build(index.read().map_err(|e| e.to_string())?));
+        let swarm = RwLock::new(index.swarm().map_err(|e| e.to_string())?);
         Ok(Self {
+            content: match content {
+                Some(c) => {
+                    if !c.is_dir() {
+                        return Err("Public content root is not directory".into());
+                    }
+                    c.canonicalize().map_err(|e| e.to_string())?
+                }
+                None => root.clone(),
+            },
             default_capacity,
             default_limit,
-            root: root.canonicalize().map_err(|e| e.to_string())?,
+            hide_inactive,
+            hide_spam,
+            index,
+            pending,
+            root,
+            search,
+            swarm,
+            _watcher: watcher,
         })
     }
 
     // Getters
 
     pub fn torrent(&self, info_hash: Id20) -> Option<Torrent> {
-        let mut p = PathBuf::from(&self.root);
-        p.push(format!("{}.{E}", info_hash.as_string()));
-        Some(Torrent {
-            bytes: fs::read(&p).ok()?,
-            time: p.metadata().ok()?.modified().ok()?.into(),
-        })
+        let p = Layout::find(&self.root, &info_hash.as_string(), &format!(".{E}"))?;
+        Torrent::read(&p, p.metadata().ok()?.modified().ok()?).ok()
     }
 
+    /// Get the hybrid torrent by its v2 `info_hash`
+    pub fn torrent_v2(&self, info_hash: Id32) -> Option<Torrent> {
+        let t = self.torrent(Aliases::open(&self.root).get(&info_hash.as_string())?)?;
+        if InfoHashes::from_bytes(&t.bytes).ok()?.v2 == Some(info_hash) {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    /// * `source` keeps the torrents listed by the full-scrape source of given name only
+    /// * `query` keeps the matching torrents only, see `Query` for the syntax
+    /// * the relevance score of every torrent is returned for the text `query` terms
     pub async fn torrents<F, Fut>(
         &self,
-        keyword: Option<&str>,
+        query: Option<&Query>,
+        source: Option<&str>,
         sort_order: Option<(Sort, Order)>,
         start: Option<usize>,
         limit: Option<usize>,
@@ -79,21 +284,21 @@ impl Storage {
         F: Fn(Id20) -> Fut,
         Fut: Future<Output = bool>,
     {
-        let f = self.files(keyword, sort_order)?;
+        let f = self.records(query, source, sort_order)?;
         let t = f.len(); // total
         let l = limit.unwrap_or(t);
         let s = start.unwrap_or_default();
         let mut b = Vec::with_capacity(l);
         let mut i = 0; // start offset
-        for file in f.iter() {
-            if let Some(n) = file.path.file_stem()
-                && let Ok(id20) = Id20::from_str(&n.to_string_lossy())
+        for (record, relevance) in f {
+            if let Some(id20) = record.id20()
                 && visibility_filter(id20).await
             {
                 if i >= s && b.len() < l {
-                    b.push(Torrent {
-                        bytes: fs::read(&file.path)?,
-                        time: file.modified.into(),
+                    b.push(Entry {
+                        meta: self.meta(&record.info_hash),
+                        record,
+                        relevance,
                     });
                 }
                 i += 1;
@@ -106,18 +311,34 @@ impl Storage {
         })
     }
 
-    /// Build URI for given `path`
+    /// Get integrity status of the preloaded files (empty if not checked)
+    pub fn verification(&self, info_hash: &str) -> Verification {
+        Layout::find(&self.root, info_hash, &format!(".{}", verify::E))
+            .and_then(|p| Verification::read(&p).ok())
+            .unwrap_or_default()
+    }
+
+    /// Get swarm statistics recorded by the crawler for given `info_hash`, ordered by time
+    pub fn history(&self, info_hash: &str) -> Vec<Sample> {
+        History::open(&self.root).get(info_hash).unwrap_or_default()
+    }
+
+    /// Build URI for given `path` of the preloaded content
     ///
     /// * result requires URL encode
+    /// * the URI is relative to the `content` root
     pub fn href(&self, info_hash: &str, path: &str) -> Option<String> {
-        let mut relative = PathBuf::from(info_hash);
+        let mut relative = Layout::find(&self.content, info_hash, "")?
+            .strip_prefix(&self.content)
+            .ok()?
+            .to_path_buf();
         relative.push(path);
 
-        let mut absolute = PathBuf::from(&self.root);
+        let mut absolute = PathBuf::from(&self.content);
         absolute.push(&relative);
 
         let c = absolute.canonicalize().ok()?;
-        if c.starts_with(&self.root) && c.exists() {
+        if c.starts_with(&self.content) && c.exists() {
             Some(relative.to_string_lossy().into())
         } else {
             None
@@ -126,91 +347,128 @@ impl Storage {
 
     /// Return canonical absolute path to file
     ///
+    /// * lookup the `root` first, then the `content` root
     /// * `None` if the given URI does not exist or has denied location
     pub fn filepath(&self, relative: &str) -> Option<PathBuf> {
-        let mut p = PathBuf::from(&self.root);
-        p.push(relative);
+        [&self.root, &self.content].into_iter().find_map(|root| {
+            let mut p = PathBuf::from(root);
+            p.push(relative);
 
-        let c = p.canonicalize().ok()?;
-        if c.starts_with(&self.root) && c.is_file() {
-            Some(c)
-        } else {
-            None
-        }
+            let c = p.canonicalize().ok()?;
+            if c.starts_with(root) && c.is_file() {
+                Some(c)
+            } else {
+                None
+            }
+        })
     }
 
     // Helpers
 
-    fn files(
+    fn records(
         &self,
-        keyword: Option<&str>,
+        query: Option<&Query>,
+        source: Option<&str>,
         sort_order: Option<(Sort, Order)>,
-    ) -> Result<Vec<File>, Error> {
-        let mut files = Vec::with_capacity(self.default_capacity);
-        for dir_entry in fs::read_dir(&self.root)? {
-            let entry = dir_entry?;
-            let path = entry.path();
-            if !path.is_file() || path.extension().is_none_or(|e| e != E) {
-                continue;
-            }
-            if let Some(k) = keyword
-                && !k.trim_matches(S).is_empty()
-                && !librqbit_core::torrent_metainfo::torrent_from_bytes(&fs::read(&path)?)
-                    .is_ok_and(|m: librqbit_core::torrent_metainfo::TorrentMetaV1Owned| {
-                        k.split(S)
-                            .filter(|s| !s.is_empty())
-                            .map(|s| s.trim().to_lowercase())
-                            .all(|q| {
-                                m.info_hash.as_string().to_lowercase().contains(&q)
-                                    || m.info
-                                        .name
-                                        .as_ref()
-                                        .is_some_and(|n| n.to_string().to_lowercase().contains(&q))
-                                    || m.comment
-                                        .as_ref()
-                                        .is_some_and(|c| c.to_string().to_lowercase().contains(&q))
-                                    || m.created_by
-                                        .as_ref()
-                                        .is_some_and(|c| c.to_string().to_lowercase().contains(&q))
-                                    || m.publisher
-                                        .as_ref()
-                                        .is_some_and(|p| p.to_string().to_lowercase().contains(&q))
-                                    || m.publisher_url
-                                        .as_ref()
-                                        .is_some_and(|u| u.to_string().to_lowercase().contains(&q))
-                                    || m.announce
-                                        .as_ref()
-                                        .is_some_and(|a| a.to_string().to_lowercase().contains(&q))
-                                    || m.announce_list.iter().any(|l| {
-                                        l.iter().any(|a| a.to_string().to_lowercase().contains(&q))
-                                    })
-                                    || m.info.files.as_ref().is_some_and(|f| {
-                                        f.iter().any(|f| {
-                                            let mut p = PathBuf::new();
-                                            f.full_path(&mut p).is_ok_and(|_| {
-                                                p.to_string_lossy().to_lowercase().contains(&q)
-                                            })
-                                        })
-                                    })
-                            })
-                    })
-            {
-                continue;
+    ) -> Result<Vec<Scored>, Error> {
+        self.sync()?;
+        let is_search = query.is_some_and(|q| !q.positive().is_empty());
+        let is_listed = |r: &Record| {
+            !(self.hide_inactive && r.inactive_since.is_some()
+                || self.hide_spam.is_some_and(|t| r.is_spam(t))
+                || source.is_some_and(|s| !r.is_listed_by(s)))
+        };
+        let found = {
+            let s = self.search.read().unwrap();
+            match query {
+                Some(q) if !q.is_empty() => s
+                    .find(q, is_listed)
+                    .into_iter()
+                    .map(|(r, t)| (r, is_search.then_some(t)))
+                    .collect::<Vec<_>>(),
+                _ => s.all(is_listed).into_iter().map(|r| (r, None)).collect(),
             }
-            files.push(File {
-                modified: entry.metadata()?.modified()?,
-                path,
-            })
+        };
+        let now = Utc::now();
+        let swarm = self.swarm.read().unwrap();
+        let mut records = Vec::with_capacity(self.default_capacity.min(found.len()));
+        for (r, text) in found {
+            let relevance = text.map(|t| {
+                let age = (now - r.time).num_seconds().max(0) as f32 / 86400.0;
+                let seeders = swarm.get(&r.info_hash).map_or(0, |s| s.seeders);
+                t * (1.0 + R / (1.0 + age / 30.0)) * (1.0 + B * (seeders as f32).ln_1p())
+            });
+            records.push((r, relevance))
         }
         if let Some((sort, order)) = sort_order {
+            // tie-breaking order, kept by the stable sort below
+            records.sort_by(|(a, _), (b, _)| {
+                b.time
+                    .cmp(&a.time)
+                    .then_with(|| a.info_hash.cmp(&b.info_hash))
+            });
+            let swarm = |r: &Record| swarm.get(&r.info_hash).copied().unwrap_or_default();
             match sort {
-                Sort::Modified => match order {
-                    Order::Asc => files.sort_by_key(|a| a.modified),
-                    Order::Desc => files.sort_by_key(|b| std::cmp::Reverse(b.modified)),
-                },
+                // the scores are not negative, so their bits are ordered the same way
+                Sort::Relevance if is_search => sort_by(&mut records, order, |(_, s)| {
+                    s.unwrap_or_default().to_bits()
+                }),
+                Sort::Modified | Sort::Relevance => sort_by(&mut records, order, |(r, _)| r.time),
+                Sort::Name => sort_by(&mut records, order, |(r, _)| {
+                    r.name.as_ref().map(|n| n.to_lowercase())
+                }),
+                Sort::Size => sort_by(&mut records, order, |(r, _)| r.size),
+                Sort::Files => sort_by(&mut records, order, |(r, _)| {
+                    r.files.as_ref().map_or(1, |f| f.len())
+                }),
+                Sort::Created => sort_by(&mut records, order, |(r, _)| r.created),
+                Sort::Seeders => sort_by(&mut records, order, |(r, _)| swarm(r).seeders),
+                Sort::Leechers => sort_by(&mut records, order, |(r, _)| swarm(r).leechers),
             }
         }
-        Ok(files)
+        Ok(records)
+    }
+
+    /// Apply the `index` changes reported by the watcher to the `search` index
+    fn sync(&self) -> Result<(), Error> {
+        let p = std::mem::take(&mut *self.pending.lock().unwrap());
+        if p.reload || p.swarm {
+            *self.swarm.write().unwrap() = self.index.swarm().inspect_err(|_| {
+                // try again on the next read
+                self.pending.lock().unwrap().swarm = true
+            })?;
+        }
+        if p.reload {
+            let records = self.index.read().inspect_err(|_| {
+                // try again on the next read
+                self.pending.lock().unwrap().reload = true
+            })?;
+            *self.search.write().unwrap() = Search::build(records);
+            return Ok(());
+        }
+        if p.journal.is_empty() {
+            return Ok(());
+        }
+        let mut s = self.search.write().unwrap();
+        for h in p.journal {
+            match self.index.entry(&h)? {
+                Some(index::Entry::Record(r)) => s.insert(*r),
+                Some(index::Entry::Removed) => s.remove(&h),
+                // merged into the snapshot, wait for its change
+                None => continue,
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the crawler sidecar of the `info_hash`, if exists
+    fn meta(&self, info_hash: &str) -> Option<Meta> {
+        Meta::read(&Layout::find(
+            &self.root,
+            info_hash,
+            &format!(".{}", meta::E),
+        )?)
+        .ok()
     }
 }
 
@@ -219,18 +477,46 @@ impl Storage {
 /// Torrent file extension
 const E: &str = "torrent";
 
-/// Search keyword separators
-const S: &[char] = &[
-    '_', '-', ':', ';', ',', '(', ')', '[', ']', '/', '!', '?', ' ', // @TODO make optional
-];
+/// Record with its search relevance score
+type Scored = (Arc<Record>, Option<f32>);
+
+/// Stable sort of the `records` by the `key`, computed once per record
+fn sort_by<K: Ord>(records: &mut [Scored], order: Order, key: impl Fn(&Scored) -> K) {
+    match order {
+        Order::Asc => records.sort_by_cached_key(key),
+        Order::Desc => records.sort_by_cached_key(|r| std::cmp::Reverse(key(r))),
+    }
+}
+
+/// Score boost of the just indexed torrent, halved at 30 days of age
+const R: f32 = 0.5;
+
+/// Score boost per the natural logarithm of the seeders count (+1), reported by the full-scrape
+const B: f32 = 0.25;
+
+#[derive(Default)]
+struct Pending {
+    /// The whole index has to be read again
+    reload: bool,
+    /// Changed journal entries
+    journal: HashSet<String>,
+    /// The swarm stats have to be read again
+    swarm: bool,
+}
 
-struct File {
-    modified: SystemTime,
-    path: PathBuf,
+/// Listed torrent
+pub struct Entry {
+    /// Crawler sidecar, if exists
+    pub meta: Option<Meta>,
+    /// Indexed metadata
+    pub record: Arc<Record>,
+    /// Search relevance score, if the query has text terms
+    pub relevance: Option<f32>,
 }
 
+#[derive(Default)]
 pub struct Torrents {
     pub total: usize,
     pub visible: usize,
-    pub list: Vec<Torrent>,
+    pub list: Vec<Entry>,
 }
@@ -0,0 +1,621 @@
+//! Search query language
+//!
+//! * `word` matches any field containing the word (e.g. `264` matches `x264`), all of the terms are required
+//! * `"exact phrase"` matches the consecutive words of any field
+//! * `-term` excludes the matching torrents
+//! * `term OR term` matches any of the terms
+//! * field filters:
+//!   * `name:word`, `name:"exact phrase"` - torrent name
+//!   * `file:word`, `file:"exact phrase"` - path of any file
+//!   * `ext:mkv` - extension of any file
+//!   * `size:>1GB` - total size (`B`, `KB`, `MB`, `GB` or `TB`)
+//!   * `files:<10` - number of files
+//!   * `created:2024` - torrent creation date (`YYYY`, `YYYY-MM` or `YYYY-MM-DD`)
+//!   * `indexed:>2026-01-01` - resolve date
+//!   * `hash:abcd` - v1 or v2 info-hash prefix
+//!   * `private:yes` - private torrents (or `no`)
+//! * comparison operators of the numeric and date filters: `=` (default), `>`, `>=`, `<`, `<=`
+//! * unknown fields (e.g. URL schemes) are parsed as the plain words
+
+use super::search::split;
+use crate::index::Record;
+use chrono::{DateTime, Months, NaiveDate, Utc};
+use std::{fmt, str::FromStr};
+
+/// Parsed search query
+#[derive(Debug, Default)]
+pub struct Query {
+    /// Every group is required, any clause of the group should match
+    groups: Vec<Vec<Clause>>,
+}
+
+#[derive(Debug)]
+struct Clause {
+    is_negated: bool,
+    filter: Filter,
+}
+
+#[derive(Debug)]
+enum Filter {
+    /// Words of any field
+    Words(Vec<String>),
+    /// Consecutive words of any field
+    Phrase(Vec<String>),
+    Name(Text),
+    File(Text),
+    Ext(String),
+    Size(Cmp, u64),
+    Files(Cmp, u64),
+    Created(Cmp, Period),
+    Indexed(Cmp, Period),
+    Hash(String),
+    Private(bool),
+}
+
+#[derive(Debug)]
+enum Text {
+    /// Words matched inside the value words
+    Words(Vec<String>),
+    /// Consecutive words matched exactly
+    Phrase(Vec<String>),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Cmp {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// Time range of the date filter, the `end` is excluded
+#[derive(Debug)]
+struct Period {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+/// Query syntax error, to display for the user
+#[derive(Debug)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl FromStr for Query {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut groups: Vec<Vec<Clause>> = Vec::new();
+        let mut is_or = false;
+        let mut c = s.chars().peekable();
+        loop {
+            while c.next_if(|c| c.is_whitespace()).is_some() {}
+            if c.peek().is_none() {
+                break;
+            }
+            let is_negated = c.next_if_eq(&'-').is_some();
+            let mut raw = String::new();
+            let mut quoted = None;
+            while let Some(ch) = c.next_if(|c| !c.is_whitespace()) {
+                if ch == '"' && (raw.is_empty() || raw.ends_with(':')) {
+                    let mut q = String::new();
+                    loop {
+                        match c.next() {
+                            Some('"') => break,
+                            Some(ch) => q.push(ch),
+                            None => return Err(Error(format!("unterminated quote `\"{q}`"))),
+                        }
+                    }
+                    quoted = Some(q);
+                    break;
+                }
+                raw.push(ch)
+            }
+            if !is_negated && quoted.is_none() && raw == "OR" {
+                if groups.is_empty() || is_or {
+                    return Err(Error("`OR` requires the terms on both sides".into()));
+                }
+                is_or = true;
+                continue;
+            }
+            let Some(filter) = Filter::parse(&raw, quoted)? else {
+                continue; // punctuation only
+            };
+            let clause = Clause { is_negated, filter };
+            match groups.last_mut() {
+                Some(g) if is_or => g.push(clause),
+                _ => groups.push(vec![clause]),
+            }
+            is_or = false
+        }
+        if is_or {
+            return Err(Error("`OR` requires the terms on both sides".into()));
+        }
+        Ok(Self { groups })
+    }
+}
+
+impl Query {
+    // Getters
+
+    /// Check the query has no terms (e.g. the empty string)
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Get the words of the required (not negated and not alternative) text terms,
+    /// to lookup the candidates in the index
+    pub fn required(&self) -> Vec<&str> {
+        self.groups
+            .iter()
+            .filter(|g| g.len() == 1 && !g[0].is_negated)
+            .flat_map(|g| g[0].filter.words())
+            .map(|w| w.as_str())
+            .collect()
+    }
+
+    /// Get the words of all text terms, except negated, to score the match
+    pub fn positive(&self) -> Vec<&str> {
+        self.groups
+            .iter()
+            .flatten()
+            .filter(|c| !c.is_negated)
+            .flat_map(|c| c.filter.words())
+            .map(|w| w.as_str())
+            .collect()
+    }
+
+    /// Get the words of all text terms, to lookup in the index
+    pub fn words(&self) -> Vec<&str> {
+        self.groups
+            .iter()
+            .flatten()
+            .flat_map(|c| c.filter.words())
+            .map(|w| w.as_str())
+            .collect()
+    }
+
+    /// Check the `record` matches the query
+    /// * `word` checks any field of the `record` contains the word (by the index)
+    pub fn matches(&self, record: &Record, word: impl Fn(&str) -> bool) -> bool {
+        self.groups.iter().all(|g| {
+            g.iter()
+                .any(|c| c.filter.matches(record, &word) != c.is_negated)
+        })
+    }
+}
+
+impl Filter {
+    /// Parse the `raw` term, with its `quoted` value (if any)
+    /// * returns `None` if the term has no words
+    fn parse(raw: &str, quoted: Option<String>) -> Result<Option<Self>, Error> {
+        let Some((field, value)) = raw.split_once(':') else {
+            return Ok(match quoted {
+                Some(q) => Some(Self::Phrase(words(&q, "phrase")?)),
+                None => words(raw, "").ok().map(Self::Words),
+            });
+        };
+        let value = quoted.as_deref().unwrap_or(value);
+        let text = || -> Result<Text, Error> {
+            let w = words(value, field)?;
+            Ok(if quoted.is_some() {
+                Text::Phrase(w)
+            } else {
+                Text::Words(w)
+            })
+        };
+        Ok(Some(match field.to_lowercase().as_str() {
+            "name" => Self::Name(text()?),
+            "file" => Self::File(text()?),
+            "ext" => {
+                let e = value.trim_start_matches('.').to_lowercase();
+                if e.is_empty() || !e.chars().all(|c| c.is_alphanumeric()) {
+                    return Err(invalid(field, value, "mkv"));
+                }
+                Self::Ext(e)
+            }
+            "size" => {
+                let (cmp, v) = Cmp::parse(value);
+                Self::Size(cmp, size(v).ok_or_else(|| invalid(field, value, ">1GB"))?)
+            }
+            "files" => {
+                let (cmp, v) = Cmp::parse(value);
+                Self::Files(cmp, v.parse().map_err(|_| invalid(field, value, "<10"))?)
+            }
+            "created" | "indexed" => {
+                let (cmp, v) = Cmp::parse(value);
+                let p = Period::parse(v).ok_or_else(|| invalid(field, value, ">2024-01-01"))?;
+                if field.eq_ignore_ascii_case("created") {
+                    Self::Created(cmp, p)
+                } else {
+                    Self::Indexed(cmp, p)
+                }
+            }
+            "hash" => {
+                if value.is_empty()
+                    || value.len() > 64
+                    || !value.chars().all(|c| c.is_ascii_hexdigit())
+                {
+                    return Err(invalid(field, value, "abcd"));
+                }
+                Self::Hash(value.to_lowercase())
+            }
+            "private" => Self::Private(match value.to_lowercase().as_str() {
+                "yes" | "true" | "1" => true,
+                "no" | "false" | "0" => false,
+                _ => return Err(invalid(field, value, "yes")),
+            }),
+            // e.g. URL scheme
+            _ => {
+                return Ok(words(&format!("{raw}{}", quoted.unwrap_or_default()), "")
+                    .ok()
+                    .map(Self::Words));
+            }
+        }))
+    }
+
+    /// Get the words to lookup in the index
+    fn words(&self) -> &[String] {
+        match self {
+            Self::Words(w)
+            | Self::Phrase(w)
+            | Self::Name(Text::Words(w) | Text::Phrase(w))
+            | Self::File(Text::Words(w) | Text::Phrase(w)) => w,
+            _ => &[],
+        }
+    }
+
+    fn matches(&self, record: &Record, word: &impl Fn(&str) -> bool) -> bool {
+        match self {
+            Self::Words(w) => w.iter().all(|w| word(w)),
+            Self::Phrase(p) => texts(record).any(|t| Text::Phrase(p.clone()).matches(&t)),
+            Self::Name(t) => record.name.as_ref().is_some_and(|n| t.matches(n)),
+            Self::File(t) => paths(record).any(|p| t.matches(&p)),
+            Self::Ext(e) => paths(record).any(|p| {
+                p.rsplit_once('.')
+                    .is_some_and(|(_, x)| x.eq_ignore_ascii_case(e))
+            }),
+            Self::Size(cmp, v) => cmp.apply(&record.size, v),
+            Self::Files(cmp, v) => {
+                cmp.apply(&record.files.as_ref().map_or(1, |f| f.len() as u64), v)
+            }
+            Self::Created(cmp, p) => record.created.is_some_and(|t| p.contains(*cmp, t)),
+            Self::Indexed(cmp, p) => p.contains(*cmp, record.time),
+            Self::Hash(h) => {
+                record.info_hash.starts_with(h.as_str())
+                    || record
+                        .info_hash_v2
+                        .as_ref()
+                        .is_some_and(|v| v.starts_with(h.as_str()))
+            }
+            Self::Private(p) => record.is_private == *p,
+        }
+    }
+}
+
+impl Text {
+    fn matches(&self, value: &str) -> bool {
+        let v = split(value).collect::<Vec<_>>();
+        match self {
+            Self::Words(w) => w.iter().all(|w| v.iter().any(|v| v.contains(w.as_str()))),
+            Self::Phrase(p) => v.windows(p.len()).any(|v| v == p.as_slice()),
+        }
+    }
+}
+
+impl Cmp {
+    /// Parse the operator prefix of the `value`, return the rest
+    fn parse(value: &str) -> (Self, &str) {
+        for (p, c) in [
+            (">=", Self::Ge),
+            ("<=", Self::Le),
+            (">", Self::Gt),
+            ("<", Self::Lt),
+            ("=", Self::Eq),
+        ] {
+            if let Some(v) = value.strip_prefix(p) {
+                return (c, v);
+            }
+        }
+        (Self::Eq, value)
+    }
+
+    fn apply<T: PartialOrd>(&self, a: &T, b: &T) -> bool {
+        match self {
+            Self::Eq => a == b,
+            Self::Gt => a > b,
+            Self::Ge => a >= b,
+            Self::Lt => a < b,
+            Self::Le => a <= b,
+        }
+    }
+}
+
+impl Period {
+    /// Parse `YYYY`, `YYYY-MM` or `YYYY-MM-DD` date to the period of its precision
+    fn parse(value: &str) -> Option<Self> {
+        let mut p = value.splitn(3, '-');
+        let y = p.next()?.parse().ok()?;
+        let m = p.next().map(|m| m.parse().ok()).unwrap_or(Some(1))?;
+        let d = p.next().map(|d| d.parse().ok()).unwrap_or(Some(1))?;
+        let start = NaiveDate::from_ymd_opt(y, m, d)?;
+        let end = match value.matches('-').count() {
+            0 => start.checked_add_months(Months::new(12))?,
+            1 => start.checked_add_months(Months::new(1))?,
+            _ => start.succ_opt()?,
+        };
+        Some(Self {
+            start: start.and_hms_opt(0, 0, 0)?.and_utc(),
+            end: end.and_hms_opt(0, 0, 0)?.and_utc(),
+        })
+    }
+
+    fn contains(&self, cmp: Cmp, time: DateTime<Utc>) -> bool {
+        match cmp {
+            Cmp::Eq => self.start <= time && time < self.end,
+            Cmp::Gt => time >= self.end,
+            Cmp::Ge => time >= self.start,
+            Cmp::Lt => time < self.start,
+            Cmp::Le => time < self.end,
+        }
+    }
+}
+
+// Local members
+
+/// Split the `value` of the `field` to words, fail if there are no words
+fn words(value: &str, field: &str) -> Result<Vec<String>, Error> {
+    let w = split(value).collect::<Vec<_>>();
+    if w.is_empty() {
+        Err(Error(if field.is_empty() {
+            "empty term".into()
+        } else {
+            format!("empty `{field}` value")
+        }))
+    } else {
+        Ok(w)
+    }
+}
+
+fn invalid(field: &str, value: &str, example: &str) -> Error {
+    Error(format!(
+        "invalid `{field}` value `{value}`, expected e.g. `{field}:{example}`"
+    ))
+}
+
+/// Parse the size with optional unit (e.g. `1.5GB`) to bytes
+fn size(value: &str) -> Option<u64> {
+    let v = value.to_uppercase();
+    let (n, m) = [
+        ("TB", 1u64 << 40),
+        ("GB", 1 << 30),
+        ("MB", 1 << 20),
+        ("KB", 1 << 10),
+        ("B", 1),
+    ]
+    .into_iter()
+    .find_map(|(u, m)| v.strip_suffix(u).map(|n| (n.to_string(), m)))
+    .unwrap_or((v, 1));
+    let n: f64 = n.trim().parse().ok()?;
+    if n.is_finite() && n >= 0.0 {
+        Some((n * m as f64) as u64)
+    } else {
+        None
+    }
+}
+
+/// Get the text fields of the `record`
+fn texts(record: &Record) -> impl Iterator<Item = String> + '_ {
+    [
+        &record.name,
+        &record.comment,
+        &record.created_by,
+        &record.publisher,
+        &record.publisher_url,
+    ]
+    .into_iter()
+    .flatten()
+    .cloned()
+    .chain(record.announce.iter().cloned())
+    .chain(paths(record))
+}
+
+/// Get the file paths of the `record`, or the name of the single-file torrent
+fn paths(record: &Record) -> Box<dyn Iterator<Item = String> + '_> {
+    match record.files {
+        Some(ref f) => Box::new(
+            f.iter()
+                .filter_map(|f| f.path.as_ref().map(|p| p.to_string_lossy().to_string())),
+        ),
+        None => Box::new(record.name.iter().cloned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::File;
+    use std::path::PathBuf;
+
+    fn record() -> Record {
+        Record {
+            info_hash: "bb3cdc1f4a1e6e4bbaa06d4ae4b3cbb6a1a9b6f2".into(),
+            info_hash_v2: None,
+            name: Some("Ubuntu 24.04 Desktop amd64".into()),
+            comment: Some("Official release".into()),
+            created_by: None,
+            created: DateTime::parse_from_rfc3339("2024-04-25T12:00:00Z")
+                .ok()
+                .map(|t| t.to_utc()),
+            publisher: None,
+            publisher_url: None,
+            announce: vec!["https://torrent.ubuntu.com/announce".into()],
+            is_private: false,
+            size: 6 << 30,
+            files: Some(vec![
+                File {
+                    path: Some(PathBuf::from("ubuntu-24.04-desktop-amd64.iso")),
+                    length: (6 << 30) - 1024,
+                },
+                File {
+                    path: Some(PathBuf::from("README.txt")),
+                    length: 1024,
+                },
+            ]),
+            time: DateTime::parse_from_rfc3339("2026-01-15T00:00:00Z")
+                .unwrap()
+                .to_utc(),
+            sources: Vec::new(),
+            spam: None,
+            inactive_since: None,
+        }
+    }
+
+    /// Match the `query` against the test record, with the word lookup of the index
+    fn matches(query: &str) -> bool {
+        let r = record();
+        let tokens = texts(&r)
+            .flat_map(|t| split(&t).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        query
+            .parse::<Query>()
+            .unwrap()
+            .matches(&r, |w| tokens.iter().any(|t| t.contains(w)))
+    }
+
+    fn error(query: &str) -> String {
+        query.parse::<Query>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn words() {
+        assert!(matches("ubuntu desk"));
+        assert!(matches("buntu md64"));
+        assert!(matches("UBUNTU"));
+        assert!(!matches("ubuntu debian"));
+        assert!("".parse::<Query>().unwrap().is_empty());
+        assert!("  - ".parse::<Query>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn phrase() {
+        assert!(matches("\"24 04\""));
+        assert!(matches("\"desktop amd64\""));
+        assert!(!matches("\"amd64 desktop\""));
+        assert!(matches("name:\"ubuntu 24\""));
+        assert!(!matches("name:\"official release\""));
+        assert!(matches("file:\"desktop amd64 iso\""));
+    }
+
+    #[test]
+    fn negation() {
+        assert!(matches("ubuntu -debian"));
+        assert!(!matches("ubuntu -desktop"));
+        assert!(!matches("-\"official release\""));
+        assert!(matches("-ext:mkv"));
+    }
+
+    #[test]
+    fn or() {
+        assert!(matches("debian OR ubuntu"));
+        assert!(matches("debian OR fedora OR ubuntu"));
+        assert!(!matches("debian OR fedora"));
+        assert!(!matches("ubuntu debian OR fedora"));
+        // lowercase is the plain word
+        assert!(!matches("debian or ubuntu"));
+    }
+
+    #[test]
+    fn size() {
+        assert!(matches("size:>1GB"));
+        assert!(matches("size:>=6gb"));
+        assert!(matches("size:6GB"));
+        assert!(!matches("size:<=600MB"));
+        assert!(matches("size:<6.5GB"));
+    }
+
+    #[test]
+    fn files() {
+        assert!(matches("files:2"));
+        assert!(matches("files:<3"));
+        assert!(!matches("files:>2"));
+        assert!(matches("ext:iso"));
+        assert!(matches("ext:.TXT"));
+    }
+
+    #[test]
+    fn dates() {
+        assert!(matches("created:2024"));
+        assert!(matches("created:2024-04"));
+        assert!(matches("created:2024-04-25"));
+        assert!(!matches("created:2024-04-26"));
+        assert!(matches("created:>=2024-04"));
+        assert!(!matches("created:>2024"));
+        assert!(matches("created:<2025"));
+        assert!(matches("indexed:>2025-12-31"));
+        assert!(!matches("indexed:<2026"));
+    }
+
+    #[test]
+    fn fields() {
+        assert!(matches("hash:BB3C"));
+        assert!(!matches("hash:cc"));
+        assert!(matches("private:no"));
+        assert!(!matches("private:yes"));
+        assert!(matches("name:ubuntu"));
+        assert!(matches("name:top"));
+        assert!(!matches("name:official"));
+    }
+
+    #[test]
+    fn unknown_fields() {
+        // parsed as the plain words
+        assert!(matches("https://torrent.ubuntu.com"));
+        assert!(matches("ubuntu:desktop"));
+        assert!(!matches("ubuntu:debian"));
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(error("\"ubuntu desk"), "unterminated quote `\"ubuntu desk`");
+        assert_eq!(error("OR ubuntu"), "`OR` requires the terms on both sides");
+        assert_eq!(error("ubuntu OR"), "`OR` requires the terms on both sides");
+        assert_eq!(
+            error("ubuntu OR OR debian"),
+            "`OR` requires the terms on both sides"
+        );
+        assert_eq!(
+            error("size:big"),
+            "invalid `size` value `big`, expected e.g. `size:>1GB`"
+        );
+        assert_eq!(
+            error("files:>many"),
+            "invalid `files` value `>many`, expected e.g. `files:<10`"
+        );
+        assert_eq!(
+            error("created:2024-13"),
+            "invalid `created` value `2024-13`, expected e.g. `created:>2024-01-01`"
+        );
+        assert_eq!(
+            error("hash:xyz"),
+            "invalid `hash` value `xyz`, expected e.g. `hash:abcd`"
+        );
+        assert_eq!(
+            error("private:maybe"),
+            "invalid `private` value `maybe`, expected e.g. `private:yes`"
+        );
+        assert_eq!(
+            error("ext:"),
+            "invalid `ext` value ``, expected e.g. `ext:mkv`"
+        );
+        assert_eq!(error("name:"), "empty `name` value");
+        assert_eq!(error("file:\"--\""), "empty `file` value");
+        assert_eq!(error("\"\""), "empty `phrase` value");
+    }
+}
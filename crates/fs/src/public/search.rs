@@ -0,0 +1,273 @@
+//! In-memory inverted index of the metadata records
+//!
+//! * tokens are the lowercase alphanumeric words of the name, comment, creator, publisher,
+//!   announce URLs and file paths, and the info-hashes
+//! * every query word matches the tokens containing it (e.g. `264` matches `x264`),
+//!   tokens longer than 32 bytes (e.g. info-hashes) are matched by prefix only,
+//!   see `Query` for the syntax
+//! * matches are scored by the field weight and the term frequency, partial matches
+//!   are scored lower, the name containing the exact keyword phrase is scored higher
+
+use super::query::Query;
+use crate::index::Record;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+};
+
+#[derive(Default)]
+pub struct Search {
+    /// Record slots, released on remove
+    records: Vec<Option<Arc<Record>>>,
+    /// Released slots to reuse
+    free: Vec<usize>,
+    /// Slot of the info-hash
+    slots: HashMap<String, usize>,
+    /// Slots of the token, with its frequency in every `Field`
+    tokens: BTreeMap<String, HashMap<usize, [u16; F]>>,
+    /// Tokens ending with the suffix (except the whole token), to match the words inside
+    suffixes: BTreeMap<String, HashSet<String>>,
+}
+
+impl Search {
+    // Constructors
+
+    pub fn build(records: Vec<Record>) -> Self {
+        let mut this = Self::default();
+        for r in records {
+            this.insert(r)
+        }
+        this
+    }
+
+    // Actions
+
+    /// Add or replace the `record`
+    pub fn insert(&mut self, record: Record) {
+        self.remove(&record.info_hash);
+        let slot = match self.free.pop() {
+            Some(i) => i,
+            None => {
+                self.records.push(None);
+                self.records.len() - 1
+            }
+        };
+        for (t, f) in tokens(&record) {
+            let slots = self.tokens.entry(t.clone()).or_default();
+            if slots.is_empty() {
+                for x in suffixes(&t) {
+                    self.suffixes
+                        .entry(x.to_string())
+                        .or_default()
+                        .insert(t.clone());
+                }
+            }
+            slots.insert(slot, f);
+        }
+        self.slots.insert(record.info_hash.clone(), slot);
+        self.records[slot] = Some(Arc::new(record))
+    }
+
+    pub fn remove(&mut self, info_hash: &str) {
+        let Some(slot) = self.slots.remove(info_hash) else {
+            return;
+        };
+        if let Some(record) = self.records[slot].take() {
+            for t in tokens(&record).into_keys() {
+                if let Some(s) = self.tokens.get_mut(&t) {
+                    s.remove(&slot);
+                    if s.is_empty() {
+                        self.tokens.remove(&t);
+                        for x in suffixes(&t) {
+                            if let Some(s) = self.suffixes.get_mut(x) {
+                                s.remove(&t);
+                                if s.is_empty() {
+                                    self.suffixes.remove(x);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self.free.push(slot)
+    }
+
+    // Getters
+
+    /// Get all listed records, unordered
+    /// * `is_listed` checks the listing filters of the record
+    pub fn all(&self, is_listed: impl Fn(&Record) -> bool) -> Vec<Arc<Record>> {
+        self.records
+            .iter()
+            .flatten()
+            .filter(|r| is_listed(r))
+            .cloned()
+            .collect()
+    }
+
+    /// Find the listed records matching the `query`, with their text relevance score
+    /// * unordered, all listed records match the empty `query` with zero score
+    /// * `is_listed` checks the listing filters of the record, before the `query` match
+    pub fn find(
+        &self,
+        query: &Query,
+        is_listed: impl Fn(&Record) -> bool,
+    ) -> Vec<(Arc<Record>, f32)> {
+        let mut scores: HashMap<&str, HashMap<usize, f32>> = HashMap::new();
+        for word in query.words() {
+            scores.entry(word).or_insert_with(|| self.lookup(word));
+        }
+        // lookup the candidates by the required words first
+        let mut candidates: Option<HashSet<usize>> = None;
+        for word in query.required() {
+            let s = &scores[word];
+            candidates = Some(match candidates {
+                Some(c) => c.into_iter().filter(|i| s.contains_key(i)).collect(),
+                None => s.keys().copied().collect(),
+            });
+        }
+        let candidates = match candidates {
+            Some(c) => c.into_iter().collect(),
+            None => self.slots.values().copied().collect::<Vec<_>>(),
+        };
+        let positive = query.positive();
+        let phrase = (positive.len() > 1).then(|| positive.join(" "));
+        candidates
+            .into_iter()
+            .filter_map(|i| {
+                let r = self.records[i].clone()?;
+                if !is_listed(&r)
+                    || !query.matches(&r, |w| scores.get(w).is_some_and(|s| s.contains_key(&i)))
+                {
+                    return None;
+                }
+                let s: f32 = positive
+                    .iter()
+                    .filter_map(|w| scores.get(w).and_then(|s| s.get(&i)))
+                    .sum();
+                let is_phrase = phrase.as_ref().is_some_and(|p| {
+                    r.name
+                        .as_ref()
+                        .is_some_and(|n| split(n).collect::<Vec<_>>().join(" ").contains(p))
+                });
+                Some((r, if is_phrase { s * X } else { s }))
+            })
+            .collect()
+    }
+
+    // Tools
+
+    /// Score the slots of the tokens containing the `term`
+    fn lookup(&self, term: &str) -> HashMap<usize, f32> {
+        let mut matched: HashSet<&str> = self
+            .tokens
+            .range(term.to_string()..)
+            .take_while(|(t, _)| t.starts_with(term))
+            .map(|(t, _)| t.as_str())
+            .collect();
+        for (_, tokens) in self
+            .suffixes
+            .range(term.to_string()..)
+            .take_while(|(x, _)| x.starts_with(term))
+        {
+            matched.extend(tokens.iter().map(|t| t.as_str()))
+        }
+        let mut scores: HashMap<usize, f32> = HashMap::new();
+        for token in matched {
+            let m = if token == term { 1.0 } else { P };
+            for (slot, frequency) in &self.tokens[token] {
+                *scores.entry(*slot).or_default() += m * score(frequency)
+            }
+        }
+        scores
+    }
+}
+
+/// Split the `value` to the lowercase alphanumeric words
+pub fn split(value: &str) -> impl Iterator<Item = String> {
+    value
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+}
+
+/// Collect the unique tokens of the `record`, with their frequency in every `Field`
+fn tokens(record: &Record) -> HashMap<String, [u16; F]> {
+    let mut t: HashMap<String, [u16; F]> = HashMap::new();
+    let mut push = |field: Field, value: &str| {
+        for w in split(value) {
+            let f = &mut t.entry(w).or_default()[field as usize];
+            *f = f.saturating_add(1)
+        }
+    };
+    push(Field::Other, &record.info_hash);
+    if let Some(ref h) = record.info_hash_v2 {
+        push(Field::Other, h)
+    }
+    if let Some(ref n) = record.name {
+        push(Field::Name, n)
+    }
+    if let Some(ref c) = record.comment {
+        push(Field::Comment, c)
+    }
+    for v in [&record.created_by, &record.publisher, &record.publisher_url]
+        .into_iter()
+        .flatten()
+        .chain(record.announce.iter())
+    {
+        push(Field::Other, v)
+    }
+    for f in record.files.iter().flatten() {
+        if let Some(ref p) = f.path {
+            push(Field::Path, &p.to_string_lossy())
+        }
+    }
+    t
+}
+
+/// Get the suffixes of the `token` to index, except the whole token
+/// * skip the long tokens (e.g. info-hashes), to not bloat the index
+fn suffixes(token: &str) -> impl Iterator<Item = &str> {
+    token
+        .char_indices()
+        .skip(if token.len() > I { usize::MAX } else { 1 })
+        .map(|(i, _)| &token[i..])
+}
+
+/// Score the token match by its `frequency` in every `Field`
+fn score(frequency: &[u16; F]) -> f32 {
+    frequency
+        .iter()
+        .zip(W)
+        .filter(|(n, _)| **n > 0)
+        .map(|(n, w)| w * (1.0 + (*n as f32).ln()))
+        .sum()
+}
+
+// Local members
+
+/// Indexed field of the record
+#[derive(Clone, Copy)]
+enum Field {
+    Name,
+    Comment,
+    Path,
+    /// Info-hashes, creator, publisher and announce URLs
+    Other,
+}
+
+/// Number of the `Field` variants
+const F: usize = 4;
+
+/// Match weight of the `Field`
+const W: [f32; F] = [8.0, 3.0, 2.0, 1.0];
+
+/// Weight of the partial token match
+const P: f32 = 0.5;
+
+/// Max length of the token (in bytes) to match the words inside
+const I: usize = 32;
+
+/// Score multiplier of the name containing the exact keyword phrase
+const X: f32 = 2.0;
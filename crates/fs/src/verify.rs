@@ -0,0 +1,90 @@
+//! Integrity status of the preloaded files, checked by the crawler against the torrent piece hashes
+
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter, Result as FmtResult},
+    fs,
+    io::{Error, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    /// All pieces of the file match
+    Verified,
+    /// Pieces fully covered by the file match,
+    /// but the boundary pieces shared with other (not preloaded) files could not be checked
+    Partial,
+    /// Some piece does not match, the file is not published
+    Failed,
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Verified => "verified",
+                Self::Partial => "partial",
+                Self::Failed => "failed",
+            }
+        )
+    }
+}
+
+impl FromStr for Status {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "verified" => Ok(Self::Verified),
+            "partial" => Ok(Self::Partial),
+            "failed" => Ok(Self::Failed),
+            _ => Err(format!("unknown status `{s}`")),
+        }
+    }
+}
+
+/// Status of the preloaded files by their relative torrent path
+#[derive(Default)]
+pub struct Verification(pub HashMap<PathBuf, Status>);
+
+impl Verification {
+    // Constructors
+
+    /// Read from the `{info_hash}.verify` file, if exists
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        let mut this = HashMap::new();
+        if !path.exists() {
+            return Ok(Self(this));
+        }
+        for line in fs::read_to_string(path)?.lines() {
+            if let Some((s, p)) = line.split_once('\t')
+                && let Ok(status) = Status::from_str(s)
+            {
+                this.insert(PathBuf::from(p), status);
+            }
+        }
+        Ok(Self(this))
+    }
+
+    // Actions
+
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let mut f = fs::File::create(path)?;
+        for (p, status) in self.0.iter() {
+            writeln!(f, "{status}\t{}", p.to_string_lossy())?
+        }
+        Ok(())
+    }
+
+    // Getters
+
+    pub fn get(&self, path: &Path) -> Option<Status> {
+        self.0.get(path).copied()
+    }
+}
+
+/// Verification file extension
+pub const E: &str = "verify";
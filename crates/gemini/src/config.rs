@@ -56,6 +56,11 @@ pub struct Config {
     #[arg(short = 'S', long)]
     pub storage: PathBuf,
 
+    /// Path to the preloaded content, if kept apart from the `storage` directory
+    /// * e.g. `preload.content_path` of the `btracker-crawler`
+    #[arg(long)]
+    pub content: Option<PathBuf>,
+
     /// Listing items limit
     #[arg(short, long, default_value_t = 10)]
     pub limit: usize,
@@ -63,4 +68,22 @@ pub struct Config {
     /// Default index capacity
     #[arg(short, long, default_value_t = 1000)]
     pub capacity: usize,
+
+    /// Hide delisted or dead torrents (marked by the `btracker-crawler` lifecycle policy) from listing
+    #[arg(long, default_value_t = false)]
+    pub hide_inactive: bool,
+
+    /// Flag torrents with the spam score (detected by the `btracker-crawler`) reaching given value
+    #[arg(long, default_value_t = 50)]
+    pub spam_threshold: u32,
+
+    /// Hide torrents reaching the `spam_threshold` from listing
+    #[arg(long, default_value_t = false)]
+    pub hide_spam: bool,
+
+    /// Display following full-scrape source name(s) on the index, as the sections
+    /// * the names are defined by the `btracker-crawler` config (`tracker.scrape.name`)
+    /// * any listed source is available at `/source/name` also
+    #[arg(long)]
+    pub section: Vec<String>,
 }
@@ -1,9 +1,8 @@
-use librqbit_core::torrent_metainfo::TorrentMetaV1Owned;
+use librqbit_core::{hash_id::Id32, torrent_metainfo::TorrentMetaV1Owned};
 use plurify::Plurify;
 use url::Url;
 
-pub fn files(meta: &TorrentMetaV1Owned) -> String {
-    let total = meta.info.files.as_ref().map(|f| f.len()).unwrap_or(1);
+pub fn files(total: usize) -> String {
     format!("{total} {}", total.plurify(&["file", "files", "files"]))
 }
 
@@ -36,8 +35,14 @@ pub fn total(meta: &TorrentMetaV1Owned) -> String {
     )
 }
 
-pub fn magnet(meta: &TorrentMetaV1Owned, trackers: Option<&Vec<Url>>) -> String {
+/// * `v2` info-hash of the hybrid torrent
+pub fn magnet(meta: &TorrentMetaV1Owned, v2: Option<&Id32>, trackers: Option<&Vec<Url>>) -> String {
     let mut b = format!("magnet:?xt=urn:btih:{}", meta.info_hash.as_string());
+    if let Some(h) = v2 {
+        // SHA-256 multihash prefix
+        b.push_str("&xt=urn:btmh:1220");
+        b.push_str(&h.as_string())
+    }
     if let Some(ref n) = meta.info.name {
         b.push_str("&dn=");
         b.push_str(&urlencoding::encode(&n.to_string()))
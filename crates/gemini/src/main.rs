@@ -3,7 +3,11 @@ mod format;
 mod route;
 
 use anyhow::Result;
-use btracker_fs::public::{Order, Sort, Storage, Torrent};
+use btracker_fs::{
+    hybrid::InfoHashes,
+    meta::Meta,
+    public::{Order, Sort, Storage, Torrent, query::Query},
+};
 use btracker_scrape::Buffer as Scrape;
 use config::Config;
 use librqbit_core::torrent_metainfo::{TorrentMetaV1Owned, torrent_from_bytes};
@@ -41,7 +45,15 @@ async fn main() -> Result<()> {
 
     let config = Config::parse();
     let state = Arc::new(State {
-        public: Storage::init(&config.storage, config.limit, config.capacity).unwrap(),
+        public: Storage::init(
+            &config.storage,
+            config.content.as_deref(),
+            config.limit,
+            config.capacity,
+            config.hide_inactive,
+            config.hide_spam.then_some(config.spam_threshold),
+        )
+        .unwrap(),
         scrape: Scrape::new(
             config.scrape,
             config.scrape_timeout,
@@ -51,7 +63,9 @@ async fn main() -> Result<()> {
         .unwrap(),
         format_date: config.format_date,
         name: config.name,
+        sections: config.section,
         description: config.description,
+        spam_threshold: config.spam_threshold,
         tracker: config.tracker,
     });
 
@@ -200,27 +214,48 @@ async fn response(
                 },
             }
             .into_bytes(),
-            Route::List { page, keyword } => match list(state, keyword.as_deref(), page).await {
-                Ok(data) => success::Default {
-                    data: data.as_bytes(),
-                    meta: success::default::Meta {
-                        mime: "text/gemini".to_string(),
-                    },
-                }
+            Route::List {
+                page,
+                keyword,
+                source,
+                sort,
+            } => match keyword.as_deref().map(str::parse::<Query>).transpose() {
+                // ask for the valid query again
+                Err(e) => Input::Default(input::Default {
+                    message: Some(format!("Invalid query: {e}")),
+                })
                 .into_bytes(),
-                Err(e) => {
-                    error!("Internal server error on handle peer `{peer}` request: `{e}`");
-                    failure::temporary::General {
-                        message: Some("Internal server error".to_string()),
+                Ok(query) => match list(
+                    state,
+                    keyword.as_deref(),
+                    query.as_ref(),
+                    source.as_deref(),
+                    sort,
+                    page,
+                )
+                .await
+                {
+                    Ok(data) => success::Default {
+                        data: data.as_bytes(),
+                        meta: success::default::Meta {
+                            mime: "text/gemini".to_string(),
+                        },
                     }
-                    .into_bytes()
-                }
+                    .into_bytes(),
+                    Err(e) => {
+                        error!("Internal server error on handle peer `{peer}` request: `{e}`");
+                        failure::temporary::General {
+                            message: Some("Internal server error".to_string()),
+                        }
+                        .into_bytes()
+                    }
+                },
             },
             Route::Search => Input::Default(input::Default {
                 message: Some("Keyword, file, hash...".into()),
             })
             .into_bytes(),
-            Route::Info(id) => match state.public.torrent(id) {
+            Route::Info(info_hash) => match info_hash.torrent(&state.public) {
                 Some(torrent) => match info(state, torrent).await {
                     Ok(data) => success::Default {
                         data: data.as_bytes(),
@@ -283,20 +318,45 @@ fn send(data: &[u8], stream: &mut TlsStream<TcpStream>, callback: impl FnOnce(Re
     })());
 }
 
-async fn list(state: &State, keyword: Option<&str>, page: Option<usize>) -> Result<String> {
+async fn list(
+    state: &State,
+    keyword: Option<&str>,
+    query: Option<&Query>,
+    source: Option<&str>,
+    sort: Option<(Sort, Order)>,
+    page: Option<usize>,
+) -> Result<String> {
     /// format search keyword as the pagination query
-    fn query(keyword: Option<&str>) -> String {
+    fn search(keyword: Option<&str>) -> String {
         keyword.map(|k| format!("?{}", k)).unwrap_or_default()
     }
 
+    // format source name as the sort menu path prefix
+    let source_prefix = source
+        .map(|s| format!("/source/{}", urlencoding::encode(s)))
+        .unwrap_or_default();
+
+    // format source name and sort order as the pagination path prefix
+    let prefix = match sort {
+        Some((s, o)) => format!("{source_prefix}/sort/{s}/{o}"),
+        None => source_prefix.clone(),
+    };
+
+    let (sort_by, order_by) = sort.unwrap_or(if query.is_some() {
+        (Sort::Relevance, Order::Desc)
+    } else {
+        (Sort::Modified, Order::Desc)
+    });
+
     let scrape_index: Arc<RwLock<HashMap<[u8; 20], btracker_scrape::Result>>> =
         Arc::new(RwLock::new(HashMap::new())); // scrape info-hashes once
 
     let result = state
         .public
         .torrents(
-            keyword,
-            Some((Sort::Modified, Order::Desc)),
+            query,
+            source,
+            Some((sort_by, order_by)),
             page.map(|p| if p > 0 { p - 1 } else { p } * state.public.default_limit),
             Some(state.public.default_limit),
             {
@@ -329,6 +389,11 @@ async fn list(state: &State, keyword: Option<&str>, page: Option<usize>) -> Resu
             h.push_str(" • ");
         }
 
+        if let Some(s) = source {
+            h.push_str(s);
+            h.push_str(" • ");
+        }
+
         if let Some(p) = page
             && p > 1
         {
@@ -351,30 +416,39 @@ async fn list(state: &State, keyword: Option<&str>, page: Option<usize>) -> Resu
         b.push("```\n".into());
     }
 
-    b.push("## Recent\n".into());
+    if !state.sections.is_empty() {
+        for s in &state.sections {
+            b.push(format!("=> /source/{} {s}", urlencoding::encode(s)))
+        }
+        b.push(String::new());
+    }
 
-    if result.list.is_empty() {
+    b.push(if keyword.is_some() {
+        "## Results\n".into()
+    } else {
+        "## Recent\n".into()
+    });
+
+    let has_rows = !result.list.is_empty();
+    if !has_rows {
         b.push("Nothing.\n".into())
     } else {
         let mut si = scrape_index.write().await;
         for torrent in result.list {
-            let i: TorrentMetaV1Owned = torrent_from_bytes(&torrent.bytes)?;
+            let r = torrent.record;
             b.push(format!(
                 "=> /{} {}",
-                i.info_hash.as_string(),
-                i.info
-                    .name
-                    .as_ref()
-                    .map(|n| n.to_string())
-                    .unwrap_or_default()
+                r.info_hash,
+                r.name.as_deref().unwrap_or_default()
             ));
             b.push(format!(
-                "{} • {} • {}",
-                torrent.time.format(&state.format_date),
-                format::total(&i),
-                format::files(&i)
+                "{} • {} • {}{}",
+                r.time.format(&state.format_date),
+                format::size(r.size),
+                format::files(r.files.as_ref().map_or(1, |f| f.len())),
+                badges(torrent.meta.as_ref(), state.spam_threshold)
             ));
-            if let Some(s) = si.remove(&i.info_hash.0) {
+            if let Some(s) = r.id20().and_then(|i| si.remove(&i.0)) {
                 b.push(format!(
                     " • ↑ {} ↓ {} ⏲ {}",
                     s.complete, s.downloaded, s.incomplete
@@ -404,9 +478,9 @@ async fn list(state: &State, keyword: Option<&str>, page: Option<usize>) -> Resu
 
     if page.unwrap_or(1) * state.public.default_limit < result.visible {
         b.push(format!(
-            "=> /{}{} Next",
+            "=> {prefix}/{}{} Next",
             page.map_or(2, |p| p + 1),
-            query(keyword)
+            search(keyword)
         ))
     }
 
@@ -414,14 +488,42 @@ async fn list(state: &State, keyword: Option<&str>, page: Option<usize>) -> Resu
         b.push(format!(
             "=> {}{} Back",
             if p > 2 {
-                format!("/{}", p - 1)
+                format!("{prefix}/{}", p - 1)
+            } else if !prefix.is_empty() {
+                prefix.clone()
             } else {
                 "/".into()
             },
-            query(keyword)
+            search(keyword)
         ))
     }
 
+    if has_rows {
+        b.push("\n## Sort\n".into());
+        for s in Sort::ALL
+            .into_iter()
+            .filter(|s| query.is_some() || *s != Sort::Relevance)
+        {
+            // the active sort link toggles the order
+            if s == sort_by {
+                let (o, a) = match order_by {
+                    Order::Asc => (Order::Desc, "↑"),
+                    Order::Desc => (Order::Asc, "↓"),
+                };
+                b.push(format!(
+                    "=> {source_prefix}/sort/{s}/{o}{} {s} {a}",
+                    search(keyword)
+                ))
+            } else {
+                b.push(format!(
+                    "=> {source_prefix}/sort/{s}/{}{} {s}",
+                    s.order(),
+                    search(keyword)
+                ))
+            }
+        }
+    }
+
     b.push("\n=> /search Search".into());
 
     Ok(b.join("\n"))
@@ -442,6 +544,9 @@ async fn info(state: &State, torrent: Torrent) -> Result<String> {
     }
 
     let i: TorrentMetaV1Owned = torrent_from_bytes(&torrent.bytes)?;
+    let v2 = InfoHashes::from_bytes(&torrent.bytes)
+        .map_err(anyhow::Error::msg)?
+        .v2;
 
     let mut b = Vec::new();
 
@@ -457,20 +562,57 @@ async fn info(state: &State, torrent: Torrent) -> Result<String> {
 
     let t = state.scrape.get(&[i.info_hash.0]).await.unwrap_or_default();
     b.push(format!(
-        "{} • {} • {} • ↑ {} ↓ {} ⏲ {}\n",
+        "{} • {} • {} • ↑ {} ↓ {} ⏲ {}{}\n",
         torrent.time.format(&state.format_date),
         format::total(&i),
-        format::files(&i),
+        format::files(i.info.files.as_ref().map_or(1, |f| f.len())),
         t.complete,
         t.downloaded,
-        t.incomplete
+        t.incomplete,
+        badges(torrent.meta.as_ref(), state.spam_threshold)
     ));
 
+    if let Some(ref m) = torrent.meta {
+        let mut p = Vec::new();
+        if let Some(t) = m.first_seen {
+            p.push(format!("seen {}", t.format(&state.format_date)))
+        }
+        if !m.sources.is_empty() {
+            p.push(format!("sources: {}", m.sources.join(", ")))
+        }
+        p.push(format!("peers: {}", m.peers));
+        b.push(format!("{}\n", p.join(" • ")));
+    }
+
+    if let Some(ref h) = v2 {
+        b.push(format!(
+            "info-hash v1: {}\ninfo-hash v2: {}\n",
+            i.info_hash.as_string(),
+            h.as_string()
+        ));
+    }
+
     b.push(format!(
         "=> {} Magnet\n",
-        format::magnet(&i, state.tracker.as_ref())
+        format::magnet(&i, v2.as_ref(), state.tracker.as_ref())
     ));
 
+    let h = state.public.history(&i.info_hash.as_string());
+    if !h.is_empty() {
+        b.push("## History\n".into());
+        // most recent records first @TODO optional limit
+        for s in h.iter().rev().take(10) {
+            b.push(format!(
+                "* {} • ↑ {} ↓ {} ⏲ {}",
+                s.time.format(&state.format_date),
+                s.seeders,
+                s.downloaded,
+                s.leechers
+            ))
+        }
+        b.push(String::new())
+    }
+
     if let Some(files) = i.info.files.map(|files| {
         let mut b = Vec::with_capacity(files.len());
         for f in files {
@@ -490,16 +632,23 @@ async fn info(state: &State, torrent: Torrent) -> Result<String> {
         b
     }) {
         b.push("## Files\n".into());
+        let v = state.public.verification(&i.info_hash.as_string());
         for file in files {
             let p = file.path();
+            let s = file
+                .path
+                .as_ref()
+                .and_then(|p| v.get(p))
+                .map(|s| format!(" [{s}]"))
+                .unwrap_or_default();
             b.push(match state.public.href(&i.info_hash.as_string(), &p) {
                 Some(href) => format!(
-                    "=> {} {} ({})",
+                    "=> {} {} ({}){s}",
                     urlencoding::encode(&href),
                     p,
                     format::size(file.length)
                 ),
-                None => format!("{} ({})", p, format::size(file.length)), // * ?
+                None => format!("{} ({}){s}", p, format::size(file.length)), // * ?
             })
         }
     }
@@ -507,11 +656,30 @@ async fn info(state: &State, torrent: Torrent) -> Result<String> {
     Ok(b.join("\n"))
 }
 
+/// Format the lifecycle state and spam badges of the torrent
+fn badges(meta: Option<&Meta>, spam_threshold: u32) -> String {
+    let mut b = String::new();
+    if let Some(m) = meta {
+        if let Some(s) = m.inactive() {
+            b.push_str(&format!(" • {s}"))
+        }
+        if m.is_spam(spam_threshold)
+            && let Some(ref s) = m.spam
+        {
+            b.push_str(&format!(" • spam: {}", s.rules.join(", ")))
+        }
+    }
+    b
+}
+
 struct State {
     description: Option<String>,
     format_date: String,
     name: String,
     public: Storage,
     scrape: Scrape,
+    /// Full-scrape source names to display as the index sections
+    sections: Vec<String>,
+    spam_threshold: u32,
     tracker: Option<Vec<url::Url>>,
 }
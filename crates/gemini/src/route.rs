@@ -1,14 +1,17 @@
-use btracker_fs::public::Storage;
-use librqbit_core::Id20;
+use btracker_fs::public::{Order, Sort, Storage, Torrent};
+use librqbit_core::{Id20, hash_id::Id32};
 use regex::Regex;
 use std::{path::PathBuf, str::FromStr};
 use url::Url;
 
 pub enum Route {
     File(PathBuf),
-    Info(Id20),
+    Info(InfoHash),
     List {
         keyword: Option<String>,
+        /// Full-scrape source name
+        source: Option<String>,
+        sort: Option<(Sort, Order)>,
         page: Option<usize>,
     },
     NotFound,
@@ -24,6 +27,8 @@ impl Route {
         if p.is_empty() {
             return Self::List {
                 keyword: None,
+                source: None,
+                sort: None,
                 page: None,
             };
         }
@@ -32,17 +37,43 @@ impl Route {
             return Self::File(path);
         }
 
+        if t.len() == 64
+            && let Ok(id) = Id32::from_str(t)
+        {
+            return Self::Info(InfoHash::V2(id));
+        }
+
         if let Ok(id) = Id20::from_str(t) {
-            return Self::Info(id);
+            return Self::Info(InfoHash::V1(id));
         }
 
         if p == "/search" && q.is_none() {
             return Self::Search;
         }
 
+        if let Some(c) =
+            Regex::new(r"^(?:/source/([^/]+))?(?:/sort/([a-z]+)/(asc|desc))?(?:/(\d+))?$")
+                .unwrap()
+                .captures(&p)
+            && (c.get(1).is_some() || c.get(2).is_some())
+        {
+            return Self::List {
+                keyword: q.and_then(|k| urlencoding::decode(k).ok().map(|k| k.into())),
+                source: c.get(1).map(|s| s.as_str().into()),
+                // unknown values fallback to the default
+                sort: c
+                    .get(2)
+                    .zip(c.get(3))
+                    .and_then(|(s, o)| Some((s.as_str().parse().ok()?, o.as_str().parse().ok()?))),
+                page: c.get(4).map(|p| p.as_str().parse::<usize>().unwrap_or(1)),
+            };
+        }
+
         if Regex::new(r"^/(|search)").unwrap().is_match(&p) {
             return Self::List {
                 keyword: q.and_then(|k| urlencoding::decode(k).ok().map(|k| k.into())),
+                source: None,
+                sort: None,
                 page: Regex::new(r"/(\d+)$").unwrap().captures(&p).map(|c| {
                     c.get(1)
                         .map_or(1, |p| p.as_str().parse::<usize>().unwrap_or(1))
@@ -53,3 +84,18 @@ impl Route {
         Self::NotFound
     }
 }
+
+/// v1 (40 chars) or v2 (64 chars) info-hash
+pub enum InfoHash {
+    V1(Id20),
+    V2(Id32),
+}
+
+impl InfoHash {
+    pub fn torrent(&self, public: &Storage) -> Option<Torrent> {
+        match self {
+            Self::V1(i) => public.torrent(*i),
+            Self::V2(i) => public.torrent_v2(*i),
+        }
+    }
+}
@@ -16,6 +16,13 @@ pub struct Config {
     #[arg(long)]
     pub public: PathBuf,
 
+    /// Path to the preloaded content, if kept apart from the `public` directory
+    ///
+    /// * e.g. `preload.content_path` of the `btracker-crawler`
+    /// * the files are served at the same URI as from the `public` directory
+    #[arg(long)]
+    pub content: Option<PathBuf>,
+
     /// Server name
     /// * append also to the torrent files as a comment
     #[arg(long, default_value_t = String::from("βtracker"))]
@@ -51,6 +58,24 @@ pub struct Config {
     #[arg(long, default_value_t = 1000)]
     pub capacity: usize,
 
+    /// Hide delisted or dead torrents (marked by the `btracker-crawler` lifecycle policy) from listing
+    #[arg(long, default_value_t = false)]
+    pub hide_inactive: bool,
+
+    /// Flag torrents with the spam score (detected by the `btracker-crawler`) reaching given value
+    #[arg(long, default_value_t = 50)]
+    pub spam_threshold: u32,
+
+    /// Hide torrents reaching the `spam_threshold` from listing
+    #[arg(long, default_value_t = false)]
+    pub hide_spam: bool,
+
+    /// Display following full-scrape source name(s) in the header, as the index sections
+    /// * the names are defined by the `btracker-crawler` config (`tracker.scrape.name`)
+    /// * any listed source is available at `/?source=name` also
+    #[arg(long)]
+    pub section: Vec<String>,
+
     /// Bind server on given host
     #[arg(long, default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
     pub host: IpAddr,
@@ -1,4 +1,5 @@
-use librqbit_core::Id20;
+use btracker_fs::public::{self, Storage};
+use librqbit_core::{Id20, hash_id::Id32};
 use std::str::FromStr;
 
 pub struct Torrent(Id20);
@@ -21,19 +22,32 @@ impl<'r> rocket::request::FromParam<'r> for Torrent {
     }
 }
 
-pub struct InfoHash(Id20);
+/// v1 (40 chars) or v2 (64 chars) info-hash
+pub enum InfoHash {
+    V1(Id20),
+    V2(Id32),
+}
 
 impl InfoHash {
-    pub fn id20(&self) -> Id20 {
-        self.0
+    pub fn torrent(&self, storage: &Storage) -> Option<public::Torrent> {
+        match self {
+            Self::V1(i) => storage.torrent(*i),
+            Self::V2(i) => storage.torrent_v2(*i),
+        }
     }
 }
 
 impl<'r> rocket::request::FromParam<'r> for InfoHash {
     type Error = String;
     fn from_param(param: &'r str) -> Result<Self, Self::Error> {
-        Ok(Self(
-            <Id20 as FromStr>::from_str(param).map_err(|e| e.to_string())?,
-        ))
+        if param.len() == 64 {
+            Ok(Self::V2(
+                <Id32 as FromStr>::from_str(param).map_err(|e| e.to_string())?,
+            ))
+        } else {
+            Ok(Self::V1(
+                <Id20 as FromStr>::from_str(param).map_err(|e| e.to_string())?,
+            ))
+        }
     }
 }
@@ -9,7 +9,7 @@ mod torrent;
 
 use std::sync::Arc;
 
-use btracker_fs::public::{Order, Sort, Storage};
+use btracker_fs::public::{Order, Sort, Storage, Torrents, query::Query};
 use btracker_scrape::Buffer as Scrape;
 use config::Config;
 use feed::Feed;
@@ -17,7 +17,10 @@ use info_hash::InfoHash;
 use meta::Meta;
 use rocket::{
     State,
-    http::{ContentType, Header, Status},
+    http::{
+        ContentType, Header, Status,
+        uri::fmt::{self, Formatter, FromUriParam, UriDisplay},
+    },
     response::{Responder, Response, content::RawXml},
     serde::Serialize,
     tokio::sync::RwLock,
@@ -25,16 +28,50 @@ use rocket::{
 use rocket_dyn_templates::{Template, context};
 use torrent::Torrent;
 
-#[get("/?<search>&<page>")]
-async fn index(
-    search: Option<&str>,
+/// Query string of the index page
+#[derive(Clone, Copy, FromForm)]
+struct Index<'r> {
+    search: Option<&'r str>,
+    source: Option<&'r str>,
     page: Option<usize>,
+    sort: Option<&'r str>,
+    order: Option<&'r str>,
+}
+
+impl UriDisplay<fmt::Query> for Index<'_> {
+    fn fmt(&self, f: &mut Formatter<'_, fmt::Query>) -> std::fmt::Result {
+        f.write_named_value("search", self.search)?;
+        f.write_named_value("source", self.source)?;
+        f.write_named_value("page", self.page)?;
+        f.write_named_value("sort", self.sort)?;
+        f.write_named_value("order", self.order)
+    }
+}
+
+impl<'r> FromUriParam<fmt::Query, Index<'r>> for Index<'r> {
+    type Target = Index<'r>;
+    fn from_uri_param(param: Index<'r>) -> Self::Target {
+        param
+    }
+}
+
+#[get("/?<params..>")]
+async fn index(
+    params: Index<'_>,
     scrape: &State<Scrape>,
     storage: &State<Storage>,
     meta: &State<Meta>,
 ) -> Result<Template, Status> {
     use std::collections::HashMap;
 
+    let Index {
+        search,
+        source,
+        page,
+        sort,
+        order,
+    } = params;
+
     #[derive(rocket::serde::Serialize, Default)]
     #[serde(crate = "rocket::serde")]
     pub struct S {
@@ -48,7 +85,9 @@ async fn index(
     struct R {
         created: Option<String>,
         files: Option<usize>,
+        inactive: Option<&'static str>,
         indexed: String,
+        spam: Option<String>,
         magnet: String,
         torrent: String,
         scrape: Option<S>,
@@ -59,66 +98,96 @@ async fn index(
     let scrape_index: Arc<RwLock<HashMap<librqbit_core::Id20, S>>> =
         Arc::new(RwLock::new(HashMap::new())); // scrape info-hashes once
 
-    let result = storage
-        .torrents(
-            search,
-            Some((Sort::Modified, Order::Desc)),
-            page.map(|p| if p > 0 { p - 1 } else { p } * storage.default_limit),
-            Some(storage.default_limit),
-            {
-                let si = scrape_index.clone();
-                let is_search = search.is_some();
+    // syntax error is shown instead of the results
+    let (query, error) = match search
+        .filter(|q| !q.is_empty())
+        .map(str::parse::<Query>)
+        .transpose()
+    {
+        Ok(q) => (q, None),
+        Err(e) => (None, Some(e.to_string())),
+    };
 
-                move |id20| {
-                    let si = si.clone();
-                    async move {
-                        if let Ok(s) = scrape.get(&[id20.0]).await {
-                            let is_active = s.incomplete > 0 || s.downloaded > 0 || s.complete > 0;
-                            assert!(
-                                si.write()
-                                    .await
-                                    .insert(
-                                        id20,
-                                        S {
-                                            leechers: s.incomplete,
-                                            peers: s.downloaded,
-                                            seeders: s.complete
-                                        }
-                                    )
-                                    .is_none()
-                            );
-                            is_search || is_active
-                        } else {
-                            is_search
+    // unknown values fallback to the defaults
+    let sort_by = sort
+        .and_then(|s| s.parse::<Sort>().ok())
+        .unwrap_or(if query.is_some() {
+            Sort::Relevance
+        } else {
+            Sort::Modified
+        });
+    let order_by = order
+        .and_then(|o| o.parse::<Order>().ok())
+        .unwrap_or(sort_by.order());
+
+    let result = if error.is_some() {
+        Torrents::default()
+    } else {
+        storage
+            .torrents(
+                query.as_ref(),
+                source.filter(|s| !s.is_empty()),
+                Some((sort_by, order_by)),
+                page.map(|p| if p > 0 { p - 1 } else { p } * storage.default_limit),
+                Some(storage.default_limit),
+                {
+                    let si = scrape_index.clone();
+                    let is_search = search.is_some();
+
+                    move |id20| {
+                        let si = si.clone();
+                        async move {
+                            if let Ok(s) = scrape.get(&[id20.0]).await {
+                                let is_active =
+                                    s.incomplete > 0 || s.downloaded > 0 || s.complete > 0;
+                                assert!(
+                                    si.write()
+                                        .await
+                                        .insert(
+                                            id20,
+                                            S {
+                                                leechers: s.incomplete,
+                                                peers: s.downloaded,
+                                                seeders: s.complete
+                                            }
+                                        )
+                                        .is_none()
+                                );
+                                is_search || is_active
+                            } else {
+                                is_search
+                            }
                         }
                     }
-                }
-            },
-        )
-        .await
-        .map_err(|e| {
-            error!("Torrents public storage read error: `{e}`");
-            Status::InternalServerError
-        })?;
+                },
+            )
+            .await
+            .map_err(|e| {
+                error!("Torrents public storage read error: `{e}`");
+                Status::InternalServerError
+            })?
+    };
 
     let mut rows = Vec::with_capacity(result.list.len());
     {
         let mut scrape_lock = scrape_index.write().await;
         for t in result.list.into_iter() {
-            match Torrent::from_public(&t.bytes, t.time) {
+            match Torrent::from_record(&t.record) {
                 Ok(this) => rows.push(R {
                     created: this
                         .creation_date
                         .map(|t| t.format(&meta.format_time).to_string()),
                     files: this.files(),
+                    inactive: t.meta.as_ref().and_then(|m| m.inactive()),
                     indexed: this.time.format(&meta.format_time).to_string(),
+                    spam: spam(t.meta.as_ref(), meta.spam_threshold),
                     magnet: this.magnet(meta.trackers.as_ref()),
                     torrent: this.torrent(), // @TODO customize trackers
                     scrape: scrape_lock.remove(&this.id),
                     size: this.size(),
                     this,
                 }),
-                Err(e) => error!("Torrent storage read error: `{e}`"),
+                Err(e) => error!("Torrent index read error: `{e}`"),
             }
         }
     }
@@ -134,28 +203,56 @@ async fn index(
                     t.push_str("Search");
                     t.push_str(S)
                 }
+                if let Some(s) = source && !s.is_empty() {
+                    t.push_str(s);
+                    t.push_str(S)
+                }
                 if let Some(p) = page && p > 1 {
                     t.push_str(&format!("Page {p}"));
                     t.push_str(S)
                 }
                 t.push_str(&meta.title);
                 if let Some(ref description) = meta.description
-                        && page.is_none_or(|p| p == 1) && search.is_none_or(|q| q.is_empty()) {
+                        && page.is_none_or(|p| p == 1) && search.is_none_or(|q| q.is_empty())
+                        && source.is_none_or(|s| s.is_empty()) {
                     t.push_str(S);
                     t.push_str(description)
                 }
                 t
             },
             meta: meta.inner(),
-            back: page.map(|p| uri!(index(search, if p > 2 { Some(p - 1) } else { None }))),
+            back: page.map(|p| uri!(index(Index { page: if p > 2 { Some(p - 1) } else { None }, ..params }))),
             next: if page.unwrap_or(1) * storage.default_limit >= result.visible { None }
-                    else { Some(uri!(index(search, Some(page.map_or(2, |p| p + 1))))) },
+                    else { Some(uri!(index(Index { page: Some(page.map_or(2, |p| p + 1)), ..params }))) },
+            sorts: Sort::ALL
+                .into_iter()
+                .filter(|s| query.is_some() || *s != Sort::Relevance)
+                .map(|s| {
+                    let is_active = s == sort_by;
+                    // the active sort link toggles the order
+                    let o = if !is_active {
+                        s.order()
+                    } else if order_by == Order::Asc {
+                        Order::Desc
+                    } else {
+                        Order::Asc
+                    };
+                    let (s, o) = (s.to_string(), o.to_string());
+                    context! {
+                        url: uri!(index(Index { page: None, sort: Some(s.as_str()), order: Some(o.as_str()), ..params })).to_string(),
+                        order: is_active.then(|| order_by.to_string()),
+                        name: s,
+                    }
+                })
+                .collect::<Vec<_>>(),
             page: page.unwrap_or(1),
             pages: (result.visible as f64 / storage.default_limit as f64).ceil(),
             total: result.total,
             visible: result.visible,
             is_search: search.is_some(),
             search,
+            error,
+            source,
             rows
         },
     ))
@@ -168,7 +265,7 @@ async fn info(
     scrape: &State<Scrape>,
     meta: &State<Meta>,
 ) -> Result<Template, Status> {
-    match storage.torrent(info_hash.id20()) {
+    match info_hash.torrent(storage) {
         Some(t) => {
             #[derive(rocket::serde::Serialize, Default)]
             #[serde(crate = "rocket::serde")]
@@ -183,6 +280,24 @@ async fn info(
                 href: Option<String>,
                 path: String,
                 size: String,
+                status: Option<String>,
+            }
+            #[derive(Serialize)]
+            #[serde(crate = "rocket::serde")]
+            struct P {
+                first_seen: Option<String>,
+                sources: Vec<String>,
+                peers: usize,
+                errors: Vec<String>,
+            }
+            #[derive(Serialize)]
+            #[serde(crate = "rocket::serde")]
+            struct H {
+                from: String,
+                to: String,
+                max: u32,
+                seeders: String,
+                leechers: String,
             }
             let this = Torrent::from_public(&t.bytes, t.time).map_err(|e| {
                 error!("Torrent parse error: `{e}`");
@@ -204,21 +319,69 @@ async fn info(
                     created: this.creation_date.map(|t| t.format(&meta.format_time).to_string()),
                     files_total: this.files(),
                     files_list: this.files.as_ref().map(|f| {
+                        let v = storage.verification(&this.info_hash);
                         f.iter()
                             .map(|f| {
                                 let p = f.path();
                                 F {
                                     href: storage.href(&this.info_hash, &p),
+                                    status: f
+                                        .path
+                                        .as_ref()
+                                        .and_then(|p| v.get(p))
+                                        .map(|s| s.to_string()),
                                     path: p,
                                     size: f.size(),
                                 }
                             })
                             .collect::<Vec<F>>()
                     }),
+                    inactive: t.meta.as_ref().and_then(|m| m.inactive()),
                     indexed: this.time.format(&meta.format_time).to_string(),
+                    spam: spam(t.meta.as_ref(), meta.spam_threshold),
                     magnet: this.magnet(meta.trackers.as_ref()),
                     torrent: this.torrent(), // @TODO customize trackers
-                    scrape: scrape.get(&[info_hash.id20().0]).await.ok().map(|s| S {
+                    history: {
+                        let h = storage.history(&this.info_hash);
+                        match (h.first(), h.last()) {
+                            (Some(a), Some(b)) if h.len() > 1 => {
+                                // SVG polyline points in the 100x24 view box
+                                let max = h
+                                    .iter()
+                                    .map(|s| s.seeders.max(s.leechers))
+                                    .max()
+                                    .unwrap_or_default();
+                                let d = (b.time - a.time).num_seconds().max(1) as f64;
+                                let p = |v: fn(&btracker_fs::history::Sample) -> u32| {
+                                    h.iter()
+                                        .map(|s| {
+                                            format!(
+                                                "{:.2},{:.2}",
+                                                (s.time - a.time).num_seconds() as f64 * 100.0 / d,
+                                                24.0 - v(s) as f64 * 24.0 / max.max(1) as f64
+                                            )
+                                        })
+                                        .collect::<Vec<String>>()
+                                        .join(" ")
+                                };
+                                Some(H {
+                                    from: a.time.format(&meta.format_time).to_string(),
+                                    to: b.time.format(&meta.format_time).to_string(),
+                                    max,
+                                    seeders: p(|s| s.seeders),
+                                    leechers: p(|s| s.leechers),
+                                })
+                            }
+                            _ => None,
+                        }
+                    },
+                    provenance: t.meta.map(|m| P {
+                        first_seen: m.first_seen.map(|t| t.format(&meta.format_time).to_string()),
+                        sources: m.sources,
+                        peers: m.peers,
+                        errors: m.errors,
+                    }),
+                    scrape: scrape.get(&[this.id.0]).await.ok().map(|s| S {
                         leechers: s.incomplete,
                         peers: s.downloaded,
                         seeders: s.complete
@@ -306,8 +469,9 @@ fn torrent_file(
     }
 }
 
-#[get("/rss")]
+#[get("/rss?<source>")]
 async fn rss(
+    source: Option<&str>,
     meta: &State<Meta>,
     scrape: &State<Scrape>,
     storage: &State<Storage>,
@@ -321,6 +485,7 @@ async fn rss(
     for t in storage
         .torrents(
             None,
+            source.filter(|s| !s.is_empty()),
             Some((Sort::Modified, Order::Desc)),
             None,
             Some(storage.default_limit),
@@ -338,8 +503,8 @@ async fn rss(
         })?
         .list
     {
-        f.push(Torrent::from_public(&t.bytes, t.time).map_err(|e| {
-            error!("Torrent parse error: `{e}`");
+        f.push(Torrent::from_record(&t.record).map_err(|e| {
+            error!("Torrent index read error: `{e}`");
             Status::InternalServerError
         })?)
     }
@@ -353,7 +518,7 @@ fn rocket() -> _ {
     if config.canonical_url.is_none() {
         warn!("Canonical URL option is required for the RSS feed by the specification!") // @TODO
     }
-    rocket::build()
+    let mut r = rocket::build()
         .attach(Template::fairing())
         .configure(rocket::Config {
             port: config.port,
@@ -373,21 +538,48 @@ fn rocket() -> _ {
             )
             .unwrap(),
         )
-        .manage(Storage::init(&config.public, config.list_limit, config.capacity).unwrap())
+        .manage(
+            Storage::init(
+                &config.public,
+                config.content.as_deref(),
+                config.list_limit,
+                config.capacity,
+                config.hide_inactive,
+                config.hide_spam.then_some(config.spam_threshold),
+            )
+            .unwrap(),
+        )
         .manage(Meta {
             canonical: config.canonical_url,
             description: config.description,
             format_time: config.format_time,
+            sections: config.section,
+            spam_threshold: config.spam_threshold,
             title: config.title,
             trackers: config.tracker,
             version: env!("CARGO_PKG_VERSION").into(),
         })
         .mount("/", rocket::fs::FileServer::from(config.public))
-        .mount("/", routes![index, rss, info, torrent_file])
+        .mount("/", routes![index, rss, info, torrent_file]);
+    // lookup the preloaded content after the `public` files
+    if let Some(content) = config.content {
+        r = r.mount(
+            "/",
+            rocket::fs::FileServer::new(content, rocket::fs::Options::None).rank(11),
+        )
+    }
+    r
 }
 
 const S: &str = " • ";
 
+/// Get the triggered spam rules of the torrent reaching the `threshold`
+fn spam(meta: Option<&btracker_fs::meta::Meta>, threshold: u32) -> Option<String> {
+    meta.filter(|m| m.is_spam(threshold))
+        .and_then(|m| m.spam.as_ref())
+        .map(|s| format!("{} ({})", s.rules.join(", "), s.score))
+}
+
 /// Downloadable .torrent bytes, with meta-info updated
 struct TorrentFile {
     name: String,
@@ -7,6 +7,10 @@ pub struct Meta {
     pub canonical: Option<Url>,
     pub description: Option<String>,
     pub format_time: String,
+    /// Full-scrape source names to display as the index sections
+    pub sections: Vec<String>,
+    /// Flag torrents with the spam score reaching this value
+    pub spam_threshold: u32,
     pub title: String,
     /// * use vector to keep the order from the arguments list
     pub trackers: Option<Vec<Url>>,
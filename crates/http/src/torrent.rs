@@ -1,11 +1,9 @@
 mod file;
 
+use btracker_fs::index::Record;
 use chrono::{DateTime, Utc};
 use file::File;
-use librqbit_core::{
-    Id20,
-    torrent_metainfo::{self, TorrentMetaV1Owned},
-};
+use librqbit_core::Id20;
 use rocket::serde::Serialize;
 
 #[derive(Clone, Debug, Serialize)]
@@ -18,6 +16,8 @@ pub struct Torrent {
     pub files: Option<Vec<File>>,
     pub id: Id20,
     pub info_hash: String,
+    /// v2 info-hash of the hybrid torrent
+    pub info_hash_v2: Option<String>,
     pub is_private: bool,
     pub length: Option<u64>,
     pub name: Option<String>,
@@ -30,47 +30,39 @@ pub struct Torrent {
 
 impl Torrent {
     pub fn from_public(bytes: &[u8], time: DateTime<Utc>) -> Result<Self, String> {
-        let i: TorrentMetaV1Owned =
-            torrent_metainfo::torrent_from_bytes(bytes).map_err(|e| e.to_string())?;
+        Self::from_record(&Record::from_bytes(bytes, time)?)
+    }
+
+    /// Build from the metadata index, without the `.torrent` file parse
+    pub fn from_record(r: &Record) -> Result<Self, String> {
         Ok(Torrent {
-            id: i.info_hash,
-            info_hash: i.info_hash.as_string(),
-            announce: i.announce.map(|a| a.to_string()),
-            comment: i.comment.map(|c| c.to_string()),
-            created_by: i.created_by.map(|c| c.to_string()),
-            creation_date: i
-                .creation_date
-                .map(|t| DateTime::from_timestamp_nanos(t as i64)),
-            size: i.info.length.unwrap_or_default()
-                + i.info
-                    .files
-                    .as_ref()
-                    .map(|files| files.iter().map(|f| f.length).sum::<u64>())
-                    .unwrap_or_default(),
-            files: i.info.files.map(|files| {
-                let mut b = Vec::with_capacity(files.len());
-                for f in files {
-                    let mut p = std::path::PathBuf::new();
-                    b.push(File {
+            id: r
+                .id20()
+                .ok_or_else(|| format!("invalid info-hash `{}`", r.info_hash))?,
+            info_hash: r.info_hash.clone(),
+            info_hash_v2: r.info_hash_v2.clone(),
+            announce: r.announce.first().cloned(),
+            comment: r.comment.clone(),
+            created_by: r.created_by.clone(),
+            creation_date: r.created,
+            size: r.size,
+            files: r.files.as_ref().map(|files| {
+                let mut b = files
+                    .iter()
+                    .map(|f| File {
                         length: f.length,
-                        path: match f.full_path(&mut p) {
-                            Ok(()) => Some(p),
-                            Err(e) => {
-                                warn!("Filename decode error: {e}");
-                                None
-                            }
-                        },
+                        path: f.path.clone(),
                     })
-                }
+                    .collect::<Vec<_>>();
                 b.sort_by(|a, b| a.path.cmp(&b.path)); // @TODO optional
                 b
             }),
-            publisher_url: i.publisher_url.map(|u| u.to_string()),
-            publisher: i.publisher.map(|p| p.to_string()),
-            is_private: i.info.private,
-            length: i.info.length,
-            name: i.info.name.map(|e| e.to_string()),
-            time,
+            publisher_url: r.publisher_url.clone(),
+            publisher: r.publisher.clone(),
+            is_private: r.is_private,
+            length: r.files.is_none().then_some(r.size),
+            name: r.name.clone(),
+            time: r.time,
         })
     }
 
@@ -86,6 +78,11 @@ impl Torrent {
 
     pub fn magnet(&self, trackers: Option<&Vec<url::Url>>) -> String {
         let mut b = format!("magnet:?xt=urn:btih:{}", self.info_hash);
+        if let Some(ref v2) = self.info_hash_v2 {
+            // SHA-256 multihash prefix
+            b.push_str("&xt=urn:btmh:1220");
+            b.push_str(v2)
+        }
         if let Some(ref n) = self.name {
             b.push_str("&dn=");
             b.push_str(&urlencoding::encode(n))